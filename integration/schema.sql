@@ -1,9 +1,45 @@
 CREATE TABLE IF NOT EXISTS listener (
     flow_id text NOT NULL,
     flows_user text NOT NULL,
-    channel_id text,
+    channel_id text NOT NULL DEFAULT '',
+    guild_id text NOT NULL DEFAULT '',
     bot_token text NOT NULL,
-    PRIMARY KEY (flow_id, flows_user)
+    bot_fingerprint text,
+    event_filter text,
+    auto_defer text,
+    PRIMARY KEY (flow_id, flows_user, channel_id, guild_id)
+);
+
+ALTER TABLE listener ADD COLUMN IF NOT EXISTS event_filter text;
+ALTER TABLE listener ADD COLUMN IF NOT EXISTS guild_id text NOT NULL DEFAULT '';
+-- Tokens are encrypted at rest and looked up by their fingerprint, rows with
+-- no fingerprint still hold a plain token and are encrypted on startup
+ALTER TABLE listener ADD COLUMN IF NOT EXISTS bot_fingerprint text;
+CREATE INDEX IF NOT EXISTS listener_bot_fingerprint ON listener (bot_fingerprint);
+-- The interactions the integration responds to before forwarding them
+ALTER TABLE listener ADD COLUMN IF NOT EXISTS auto_defer text;
+
+-- A flow used to have a single listener, it now has one per channel or guild
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM information_schema.key_column_usage
+        WHERE table_name = 'listener' AND constraint_name = 'listener_pkey'
+            AND column_name = 'guild_id'
+    ) THEN
+        UPDATE listener SET channel_id = '' WHERE channel_id IS NULL;
+        ALTER TABLE listener DROP CONSTRAINT listener_pkey;
+        ALTER TABLE listener ADD PRIMARY KEY (flow_id, flows_user, channel_id, guild_id);
+    END IF;
+END $$;
+
+-- The last error the gateway client of each bot stopped on, a fatal one
+-- stopping it until its listeners are registered again
+CREATE TABLE IF NOT EXISTS bot_status (
+    bot_fingerprint text PRIMARY KEY,
+    last_error text NOT NULL,
+    fatal boolean NOT NULL DEFAULT false,
+    failed_at timestamptz NOT NULL DEFAULT now()
 );
 
 CREATE TABLE IF NOT EXISTS guild_author (
@@ -14,4 +50,43 @@ CREATE TABLE IF NOT EXISTS guild_author (
     discord_username text NOT NULL,
     discord_email text NOT NULL,
     PRIMARY KEY (flows_user, discord_guild_id, discord_user_id)
-);
\ No newline at end of file
+);
+-- Events waiting to be delivered to the platform, in order of `seq` within
+-- the same `ordering_key` (the channel, or the guild for guild-level events)
+CREATE TABLE IF NOT EXISTS hook_outbox (
+    id bigserial PRIMARY KEY,
+    ordering_key text NOT NULL,
+    seq bigint NOT NULL,
+    event_model text NOT NULL,
+    flows text NOT NULL,
+    body text NOT NULL,
+    deferred boolean NOT NULL DEFAULT false,
+    attempts integer NOT NULL DEFAULT 0,
+    last_error text,
+    next_attempt_at timestamptz NOT NULL DEFAULT now(),
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+ALTER TABLE hook_outbox ADD COLUMN IF NOT EXISTS deferred boolean NOT NULL DEFAULT false;
+-- Delivered without waiting for the events racing with it, the interactions
+ALTER TABLE hook_outbox ADD COLUMN IF NOT EXISTS immediate boolean NOT NULL DEFAULT false;
+-- Until when the event is being delivered by one of the replicas
+ALTER TABLE hook_outbox ADD COLUMN IF NOT EXISTS leased_until timestamptz;
+
+CREATE INDEX IF NOT EXISTS hook_outbox_ordering ON hook_outbox (ordering_key, seq, id);
+
+CREATE TABLE IF NOT EXISTS hook_dead_letter (
+    id bigint PRIMARY KEY,
+    ordering_key text NOT NULL,
+    seq bigint NOT NULL,
+    event_model text NOT NULL,
+    flows text NOT NULL,
+    body text NOT NULL,
+    deferred boolean NOT NULL DEFAULT false,
+    attempts integer NOT NULL,
+    last_error text,
+    created_at timestamptz NOT NULL,
+    failed_at timestamptz NOT NULL DEFAULT now()
+);
+
+ALTER TABLE hook_dead_letter ADD COLUMN IF NOT EXISTS deferred boolean NOT NULL DEFAULT false;
@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use serenity::{
+    http::Http,
+    model::{application::interaction::InteractionResponseType, id::InteractionId},
+};
+
+/// The interactions a flow has the integration acknowledge as soon as they
+/// are received, so that Discord's 3 seconds deadline is met however long
+/// the flow takes to start.
+///
+/// The flow is then told the interaction was deferred and edits the original
+/// response or sends follow-ups instead of responding.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AutoDefer {
+    /// Defer every application command, component and modal interaction.
+    #[serde(default)]
+    pub all: bool,
+    /// The names of the application commands to defer.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<String>,
+    #[serde(default)]
+    pub components: bool,
+    #[serde(default)]
+    pub modals: bool,
+    /// Only show the deferred response of the commands and modals to their
+    /// user.
+    #[serde(default)]
+    pub ephemeral: bool,
+}
+
+pub enum DeferKind<'a> {
+    /// An application command, by name.
+    Command(&'a str),
+    Component,
+    Modal,
+}
+
+impl AutoDefer {
+    pub fn applies_to(&self, kind: &DeferKind) -> bool {
+        match kind {
+            DeferKind::Command(name) => self.all || self.commands.iter().any(|c| c == name),
+            DeferKind::Component => self.all || self.components,
+            DeferKind::Modal => self.all || self.modals,
+        }
+    }
+}
+
+/// An interaction the integration may respond to on behalf of the flows.
+pub struct Deferrable<'a> {
+    pub http: &'a Http,
+    pub id: InteractionId,
+    pub token: &'a str,
+    pub kind: DeferKind<'a>,
+}
+
+impl Deferrable<'_> {
+    /// Send the deferred response, a component only acknowledges the
+    /// interaction so that its message can be updated later.
+    ///
+    /// Returns whether Discord accepted it.
+    pub async fn defer(&self, ephemeral: bool) -> bool {
+        let response = match self.kind {
+            DeferKind::Component => json!({
+                "type": InteractionResponseType::DeferredUpdateMessage as u8,
+            }),
+            _ => json!({
+                "type": InteractionResponseType::DeferredChannelMessageWithSource as u8,
+                "data": { "flags": if ephemeral { 64 } else { 0 } },
+            }),
+        };
+
+        match self
+            .http
+            .create_interaction_response(self.id.0, self.token, &response)
+            .await
+        {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("Failed to defer interaction {}: {}", self.id, e);
+                false
+            }
+        }
+    }
+}
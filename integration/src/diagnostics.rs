@@ -0,0 +1,184 @@
+use serde::Serialize;
+use serenity::{
+    http::Http,
+    model::{
+        channel::{Channel, ChannelType, GuildChannel},
+        permissions::Permissions,
+    },
+};
+
+use crate::{filter::EventFilter, intents};
+
+/// The permissions a bot needs in a channel to receive and read its
+/// messages.
+const CHANNEL_PERMISSIONS: [(Permissions, &str); 2] = [
+    (Permissions::VIEW_CHANNEL, "View Channel"),
+    (Permissions::READ_MESSAGE_HISTORY, "Read Message History"),
+];
+
+/// A problem keeping a custom bot from receiving some of the events of a
+/// flow, returned to the flow when it registers so that its user knows what
+/// to fix.
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    #[serde(flatten)]
+    pub problem: Problem,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Problem {
+    /// A privileged intent the events of the flow need is not enabled on the
+    /// application of the bot.
+    MissingIntent { intent: &'static str },
+    /// The bot lacks permissions in a channel the flow listens to.
+    MissingPermissions {
+        channel_id: String,
+        permissions: Vec<&'static str>,
+    },
+    /// The bot can't see a channel the flow listens to, it may not be in its
+    /// server.
+    InaccessibleChannel { channel_id: String },
+    /// The bot is not in a guild the flow listens to.
+    NotInGuild { guild_id: String },
+}
+
+impl From<Problem> for Diagnostic {
+    fn from(problem: Problem) -> Self {
+        let message = match &problem {
+            Problem::MissingIntent { intent } => format!(
+                "The privileged {} intent is not enabled, enable it on the Bot page of the \
+                application in the Discord Developer Portal",
+                intent
+            ),
+            Problem::MissingPermissions {
+                channel_id,
+                permissions,
+            } => format!(
+                "The bot lacks the {} permissions in channel `{}`",
+                permissions.join(" and "),
+                channel_id
+            ),
+            Problem::InaccessibleChannel { channel_id } => format!(
+                "The bot can't see channel `{}`, invite it to the server of the channel and \
+                give it the View Channel permission",
+                channel_id
+            ),
+            Problem::NotInGuild { guild_id } => {
+                format!("The bot is not in guild `{}`, invite it there", guild_id)
+            }
+        };
+
+        Self { problem, message }
+    }
+}
+
+/// Look for what would keep the bot of `token` from receiving the events
+/// asked for by a flow listening to the channels and guilds with the
+/// `filter`.
+///
+/// Only the problems that could be established are reported, the failures
+/// to reach Discord are ignored.
+pub async fn diagnose(
+    token: &str,
+    channel_ids: &[String],
+    guild_ids: &[String],
+    filter: Option<&EventFilter>,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    if let Some(allowed) = intents::allowed(token).await {
+        let missing = intents::for_filter(filter) & !allowed;
+        for (intent, name) in intents::PRIVILEGED {
+            if missing.contains(intent) {
+                diagnostics.push(Problem::MissingIntent { intent: name }.into());
+            }
+        }
+    }
+
+    let http = Http::new(token);
+    let bot_id = match http.get_current_user().await {
+        Ok(user) => user.id,
+        Err(_) => return diagnostics,
+    };
+
+    for channel_id in channel_ids {
+        let channel = match channel_id.parse() {
+            Ok(id) => http.get_channel(id).await,
+            Err(_) => continue,
+        };
+        let channel = match channel {
+            Ok(Channel::Guild(channel)) => channel,
+            // The bot sees the private channels it is in
+            Ok(_) => continue,
+            Err(e) => {
+                if is_denied(&e) {
+                    let channel_id = channel_id.clone();
+                    diagnostics.push(Problem::InaccessibleChannel { channel_id }.into());
+                }
+                continue;
+            }
+        };
+
+        if let Some(permissions) = permissions(&http, &channel, bot_id.0).await {
+            let missing: Vec<_> = CHANNEL_PERMISSIONS
+                .iter()
+                .filter(|(p, _)| !permissions.contains(*p))
+                .map(|(_, name)| *name)
+                .collect();
+            if !missing.is_empty() {
+                diagnostics.push(
+                    Problem::MissingPermissions {
+                        channel_id: channel_id.clone(),
+                        permissions: missing,
+                    }
+                    .into(),
+                );
+            }
+        }
+    }
+
+    for guild_id in guild_ids {
+        let guild = match guild_id.parse() {
+            Ok(id) => http.get_guild(id).await,
+            Err(_) => continue,
+        };
+        if let Err(e) = guild {
+            if is_denied(&e) {
+                let guild_id = guild_id.clone();
+                diagnostics.push(Problem::NotInGuild { guild_id }.into());
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// The permissions of the bot in the channel, those of the parent channel
+/// for a thread.
+async fn permissions(http: &Http, channel: &GuildChannel, bot_id: u64) -> Option<Permissions> {
+    let parent = match channel.kind {
+        ChannelType::PublicThread | ChannelType::PrivateThread | ChannelType::NewsThread => {
+            match http.get_channel(channel.parent_id?.0).await.ok()? {
+                Channel::Guild(parent) => Some(parent),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    let channel = parent.as_ref().unwrap_or(channel);
+
+    let guild = http.get_guild(channel.guild_id.0).await.ok()?;
+    let member = http.get_member(channel.guild_id.0, bot_id).await.ok()?;
+
+    guild.user_permissions_in(channel, &member).ok()
+}
+
+/// Whether Discord refused the request, rather than failing to answer it.
+fn is_denied(e: &serenity::Error) -> bool {
+    match e {
+        serenity::Error::Http(e) => matches!(e.status_code().map(|s| s.as_u16()), Some(403 | 404)),
+        _ => false,
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use once_cell::sync::OnceCell;
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serenity::model::{id::UserId, user::User};
+
+/// The number of stored filters kept parsed, the cache is emptied beyond.
+const CACHE_CAPACITY: usize = 1024;
+
+/// The filter a flow registers along with its listener.
+///
+/// An event is only forwarded to the flow when it passes every condition
+/// that is set. Conditions on the content or the mentions only apply to the
+/// events that carry them (messages and message edits).
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EventFilter {
+    /// The `X-Discord-event-model`s to forward, empty means all of them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub event_models: Vec<String>,
+    #[serde(default)]
+    pub ignore_bots: bool,
+    /// Only forward events caused by these users, empty means everyone.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_authors: Vec<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denied_authors: Vec<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_prefix: Option<String>,
+    /// Checked when the filter is read, a filter with an invalid regex is
+    /// rejected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_regex: Option<ContentRegex>,
+    #[serde(default)]
+    pub mention_only: bool,
+}
+
+/// A regex compiled once, when the filter is read.
+#[derive(Debug)]
+pub struct ContentRegex(Regex);
+
+impl Serialize for ContentRegex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ContentRegex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let re = String::deserialize(deserializer)?;
+        Regex::new(&re).map(Self).map_err(de::Error::custom)
+    }
+}
+
+/// What the filter needs to know about an event.
+#[derive(Default)]
+pub struct EventInfo<'a> {
+    pub author_id: Option<UserId>,
+    pub author_bot: bool,
+    pub content: Option<&'a str>,
+    /// `None` for the events that can't mention anybody.
+    pub mentions_bot: Option<bool>,
+}
+
+impl<'a> EventInfo<'a> {
+    pub fn from_user(user: &User) -> Self {
+        Self {
+            author_id: Some(user.id),
+            author_bot: user.bot,
+            ..Default::default()
+        }
+    }
+}
+
+impl EventFilter {
+    /// Read a filter stored along with a listener, only parsing each one
+    /// once, as they are checked for every event.
+    pub fn cached(event_filter: &str) -> Result<Arc<Self>, serde_json::Error> {
+        static CACHE: OnceCell<Mutex<HashMap<String, Arc<EventFilter>>>> = OnceCell::new();
+        let mut cache = CACHE.get_or_init(Default::default).lock().unwrap();
+
+        if let Some(filter) = cache.get(event_filter) {
+            return Ok(filter.clone());
+        }
+
+        let filter = Arc::new(serde_json::from_str::<Self>(event_filter)?);
+        // Drop the filters of the listeners removed since
+        if cache.len() >= CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(event_filter.to_string(), filter.clone());
+
+        Ok(filter)
+    }
+
+    pub fn matches(&self, event_model: &str, info: &EventInfo) -> bool {
+        if !self.event_models.is_empty() && !self.event_models.iter().any(|m| m == event_model) {
+            return false;
+        }
+
+        if self.ignore_bots && info.author_bot {
+            return false;
+        }
+
+        let author_id = info.author_id.map(|id| id.0);
+        if !self.allowed_authors.is_empty() {
+            match author_id {
+                Some(id) if self.allowed_authors.contains(&id) => {}
+                _ => return false,
+            }
+        }
+        if let Some(id) = author_id {
+            if self.denied_authors.contains(&id) {
+                return false;
+            }
+        }
+
+        if let Some(content) = info.content {
+            if let Some(ref prefix) = self.content_prefix {
+                if !content.starts_with(prefix.as_str()) {
+                    return false;
+                }
+            }
+            if let Some(ContentRegex(ref re)) = self.content_regex {
+                if !re.is_match(content) {
+                    return false;
+                }
+            }
+        }
+
+        if self.mention_only && info.mentions_bot == Some(false) {
+            return false;
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(json: &str) -> EventFilter {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn message(author_id: u64, content: &str) -> EventInfo<'_> {
+        EventInfo {
+            author_id: Some(UserId(author_id)),
+            content: Some(content),
+            mentions_bot: Some(false),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_everything_without_conditions() {
+        let filter = EventFilter::default();
+
+        assert!(filter.matches("Message", &message(1, "hi")));
+        assert!(filter.matches("ThreadCreate", &EventInfo::default()));
+    }
+
+    #[test]
+    fn matches_the_event_models() {
+        let filter = filter(r#"{"event_models": ["Message"]}"#);
+
+        assert!(filter.matches("Message", &message(1, "hi")));
+        assert!(!filter.matches("MessageUpdate", &message(1, "hi")));
+    }
+
+    #[test]
+    fn matches_the_authors() {
+        let allowed = filter(r#"{"allowed_authors": [1]}"#);
+        let denied = filter(r#"{"denied_authors": [1]}"#);
+        let no_bots = filter(r#"{"ignore_bots": true}"#);
+        let bot = EventInfo {
+            author_bot: true,
+            ..message(2, "hi")
+        };
+
+        assert!(allowed.matches("Message", &message(1, "hi")));
+        assert!(!allowed.matches("Message", &message(2, "hi")));
+        assert!(!allowed.matches("ThreadCreate", &EventInfo::default()));
+        assert!(!denied.matches("Message", &message(1, "hi")));
+        assert!(denied.matches("Message", &message(2, "hi")));
+        assert!(!no_bots.matches("Message", &bot));
+    }
+
+    #[test]
+    fn matches_the_content_of_messages_only() {
+        let filter = filter(r#"{"content_prefix": "!", "content_regex": "^!(ping|pong)$"}"#);
+
+        assert!(filter.matches("Message", &message(1, "!ping")));
+        assert!(!filter.matches("Message", &message(1, "!pang")));
+        assert!(!filter.matches("Message", &message(1, "ping")));
+        assert!(filter.matches("ReactionAdd", &EventInfo::from_user(&User::default())));
+    }
+
+    #[test]
+    fn matches_the_mentions() {
+        let filter = filter(r#"{"mention_only": true}"#);
+        let mention = EventInfo {
+            mentions_bot: Some(true),
+            ..message(1, "hi")
+        };
+
+        assert!(filter.matches("Message", &mention));
+        assert!(!filter.matches("Message", &message(1, "hi")));
+        assert!(filter.matches("GuildMemberAddition", &EventInfo::default()));
+    }
+
+    #[test]
+    fn rejects_invalid_regexes() {
+        assert!(serde_json::from_str::<EventFilter>(r#"{"content_regex": "("}"#).is_err());
+        assert!(EventFilter::cached(r#"{"content_regex": "("}"#).is_err());
+    }
+}
@@ -1,80 +1,494 @@
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 
 use axum::async_trait;
 use serde::Serialize;
-use serenity::model::{application::interaction::Interaction, channel::Message, id::ChannelId};
+use serenity::model::{
+    application::interaction::Interaction,
+    channel::{GuildChannel, Message, Reaction},
+    event::MessageUpdateEvent,
+    gateway::Ready,
+    guild::{Member, ScheduledEvent},
+    id::{ChannelId, GuildId, MessageId},
+    user::User,
+    voice::VoiceState,
+};
 use serenity::prelude::{Context, EventHandler};
 use sqlx::PgPool;
 
-use crate::model::Flow;
-use crate::shared::get_client;
-use crate::{DEFAULT_BOT_PLACEHOLDER, HOOK_URL};
+use crate::defer::{AutoDefer, DeferKind, Deferrable};
+use crate::filter::{EventFilter, EventInfo};
+use crate::model::{Flow, FlowListener, GuildMemberRemoval, MessageDelete};
+use crate::outbox::{self, Delivery};
+use crate::DEFAULT_BOT_PLACEHOLDER;
 
 pub struct Handler {
-    pub token: String,
+    /// The fingerprint of the token the bot is identified by
+    pub fingerprint: String,
     pub pool: Arc<PgPool>,
+    /// The user id of the bot, known once the client is ready
+    pub bot_id: AtomicU64,
+}
+
+impl Handler {
+    pub fn new(fingerprint: String, pool: Arc<PgPool>) -> Self {
+        Self {
+            fingerprint,
+            pool,
+            bot_id: AtomicU64::new(0),
+        }
+    }
+
+    fn mentions_bot(&self, mentions: &[User]) -> bool {
+        let bot_id = self.bot_id.load(Ordering::Relaxed);
+        mentions.iter().any(|u| u.id.0 == bot_id)
+    }
 }
 
 #[async_trait]
 impl EventHandler for Handler {
-    async fn interaction_create(&self, _ctx: Context, interaction: Interaction) {
+    async fn ready(&self, _ctx: Context, ready: Ready) {
+        self.bot_id.store(ready.user.id.0, Ordering::Relaxed);
+    }
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
         match interaction {
             Interaction::ApplicationCommand(c) => {
-                self.send_hook(c.channel_id, &c, "ApplicationCommand").await;
+                let info = EventInfo::from_user(&c.user);
+                let interaction = Deferrable {
+                    http: &ctx.http,
+                    id: c.id,
+                    token: &c.token,
+                    kind: DeferKind::Command(&c.data.name),
+                };
+                self.send_interaction_hook(
+                    interaction,
+                    c.channel_id,
+                    c.guild_id,
+                    &c,
+                    "ApplicationCommand",
+                    info,
+                )
+                .await;
+            }
+            Interaction::MessageComponent(c) => {
+                let info = EventInfo::from_user(&c.user);
+                let interaction = Deferrable {
+                    http: &ctx.http,
+                    id: c.id,
+                    token: &c.token,
+                    kind: DeferKind::Component,
+                };
+                self.send_interaction_hook(
+                    interaction,
+                    c.channel_id,
+                    c.guild_id,
+                    &c,
+                    "MessageComponent",
+                    info,
+                )
+                .await;
+            }
+            Interaction::ModalSubmit(m) => {
+                let info = EventInfo::from_user(&m.user);
+                let interaction = Deferrable {
+                    http: &ctx.http,
+                    id: m.id,
+                    token: &m.token,
+                    kind: DeferKind::Modal,
+                };
+                self.send_interaction_hook(
+                    interaction,
+                    m.channel_id,
+                    m.guild_id,
+                    &m,
+                    "ModalSubmit",
+                    info,
+                )
+                .await;
+            }
+            // Autocompletion can't be deferred
+            Interaction::Autocomplete(a) => {
+                let info = EventInfo::from_user(&a.user);
+                self.send_hook(
+                    Some(a.channel_id),
+                    a.guild_id,
+                    Some(a.id.0),
+                    &a,
+                    "Autocomplete",
+                    info,
+                )
+                .await;
             }
             _ => {}
         }
     }
     async fn message(&self, _ctx: Context, msg: Message) {
-        self.send_hook(msg.channel_id, &msg, "Message").await;
+        let info = EventInfo {
+            content: Some(msg.content.as_str()),
+            mentions_bot: Some(self.mentions_bot(&msg.mentions)),
+            ..EventInfo::from_user(&msg.author)
+        };
+        self.send_hook(
+            Some(msg.channel_id),
+            msg.guild_id,
+            Some(msg.id.0),
+            &msg,
+            "Message",
+            info,
+        )
+        .await;
+    }
+    async fn message_update(&self, _ctx: Context, event: MessageUpdateEvent) {
+        let mut info = match event.author {
+            Some(ref author) => EventInfo::from_user(author),
+            None => EventInfo::default(),
+        };
+        info.content = event.content.as_deref();
+        info.mentions_bot = event.mentions.as_deref().map(|m| self.mentions_bot(m));
+        self.send_hook(
+            Some(event.channel_id),
+            event.guild_id,
+            None,
+            &event,
+            "MessageUpdate",
+            info,
+        )
+        .await;
+    }
+    async fn message_delete(
+        &self,
+        _ctx: Context,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        guild_id: Option<GuildId>,
+    ) {
+        let event = MessageDelete {
+            guild_id,
+            channel_id,
+            message_id,
+        };
+        self.send_hook(
+            Some(channel_id),
+            guild_id,
+            None,
+            &event,
+            "MessageDelete",
+            EventInfo::default(),
+        )
+        .await;
+    }
+    async fn reaction_add(&self, _ctx: Context, reaction: Reaction) {
+        let info = reaction_info(&reaction);
+        self.send_hook(
+            Some(reaction.channel_id),
+            reaction.guild_id,
+            None,
+            &reaction,
+            "ReactionAdd",
+            info,
+        )
+        .await;
+    }
+    async fn reaction_remove(&self, _ctx: Context, reaction: Reaction) {
+        let info = reaction_info(&reaction);
+        self.send_hook(
+            Some(reaction.channel_id),
+            reaction.guild_id,
+            None,
+            &reaction,
+            "ReactionRemove",
+            info,
+        )
+        .await;
+    }
+    async fn guild_member_addition(&self, _ctx: Context, member: Member) {
+        let info = EventInfo::from_user(&member.user);
+        self.send_hook(
+            None,
+            Some(member.guild_id),
+            None,
+            &member,
+            "GuildMemberAddition",
+            info,
+        )
+        .await;
+    }
+    async fn guild_member_removal(&self, _ctx: Context, guild_id: GuildId, user: User) {
+        let info = EventInfo::from_user(&user);
+        let event = GuildMemberRemoval { guild_id, user };
+        self.send_hook(
+            None,
+            Some(guild_id),
+            None,
+            &event,
+            "GuildMemberRemoval",
+            info,
+        )
+        .await;
+    }
+    async fn thread_create(&self, _ctx: Context, thread: GuildChannel) {
+        // Threads are routed by the channel they were created in
+        self.send_hook(
+            thread.parent_id,
+            Some(thread.guild_id),
+            None,
+            &thread,
+            "ThreadCreate",
+            EventInfo::default(),
+        )
+        .await;
+    }
+    async fn voice_state_update(&self, _ctx: Context, state: VoiceState) {
+        let info = match state.member {
+            Some(ref member) => EventInfo::from_user(&member.user),
+            None => EventInfo {
+                author_id: Some(state.user_id),
+                ..Default::default()
+            },
+        };
+        self.send_hook(
+            state.channel_id,
+            state.guild_id,
+            None,
+            &state,
+            "VoiceStateUpdate",
+            info,
+        )
+        .await;
+    }
+    async fn guild_scheduled_event_create(&self, _ctx: Context, event: ScheduledEvent) {
+        let info = scheduled_event_info(&event);
+        self.send_hook(
+            event.channel_id,
+            Some(event.guild_id),
+            None,
+            &event,
+            "GuildScheduledEventCreate",
+            info,
+        )
+        .await;
+    }
+    async fn guild_scheduled_event_update(&self, _ctx: Context, event: ScheduledEvent) {
+        let info = scheduled_event_info(&event);
+        self.send_hook(
+            event.channel_id,
+            Some(event.guild_id),
+            None,
+            &event,
+            "GuildScheduledEventUpdate",
+            info,
+        )
+        .await;
+    }
+    async fn guild_scheduled_event_delete(&self, _ctx: Context, event: ScheduledEvent) {
+        let info = scheduled_event_info(&event);
+        self.send_hook(
+            event.channel_id,
+            Some(event.guild_id),
+            None,
+            &event,
+            "GuildScheduledEventDelete",
+            info,
+        )
+        .await;
+    }
+}
+
+fn reaction_info(reaction: &Reaction) -> EventInfo<'static> {
+    match reaction.member.as_ref().and_then(|m| m.user.as_ref()) {
+        Some(user) => EventInfo::from_user(user),
+        None => EventInfo {
+            author_id: reaction.user_id,
+            ..Default::default()
+        },
+    }
+}
+
+fn scheduled_event_info(event: &ScheduledEvent) -> EventInfo<'static> {
+    match event.creator {
+        Some(ref creator) => EventInfo::from_user(creator),
+        None => EventInfo::default(),
     }
 }
 
 impl Handler {
+    /// Forward the event to the flows listening to `channel_id` or
+    /// `guild_id` whose filter lets it through.
+    ///
+    /// The event is written to the outbox, the snowflake of the event (if it
+    /// has one) keeps the events of a channel in order.
     async fn send_hook<T: Serialize + ?Sized>(
         &self,
+        channel_id: Option<ChannelId>,
+        guild_id: Option<GuildId>,
+        snowflake: Option<u64>,
+        msg: &T,
+        event_model: &str,
+        info: EventInfo<'_>,
+    ) {
+        let channel_id = channel_id.map(|c| c.as_u64().to_string());
+        let guild_id = guild_id.map(|g| g.as_u64().to_string());
+
+        let listeners = self
+            .listeners(&channel_id, &guild_id, event_model, &info)
+            .await;
+        let ordering_key = channel_id.or(guild_id).unwrap_or_default();
+        self.enqueue(
+            &listeners,
+            &ordering_key,
+            outbox::sequence(snowflake),
+            msg,
+            event_model,
+            Delivery::Settled,
+        )
+        .await;
+    }
+
+    /// Same as [`Self::send_hook`] for an interaction, which is deferred
+    /// first when one of the flows it is forwarded to asked for it.
+    ///
+    /// An interaction has to be answered within 3 seconds, so it is
+    /// delivered right away, in an ordering key of its own rather than
+    /// behind the events of its channel.
+    async fn send_interaction_hook<T: Serialize + ?Sized>(
+        &self,
+        interaction: Deferrable<'_>,
         channel_id: ChannelId,
+        guild_id: Option<GuildId>,
         msg: &T,
         event_model: &str,
+        info: EventInfo<'_>,
     ) {
-        let flows: Option<Vec<Flow>> = if self.token == DEFAULT_BOT_PLACEHOLDER {
+        let channel_id = Some(channel_id.as_u64().to_string());
+        let guild_id = guild_id.map(|g| g.as_u64().to_string());
+
+        let listeners = self
+            .listeners(&channel_id, &guild_id, event_model, &info)
+            .await;
+
+        let auto_defer = listeners
+            .iter()
+            .filter_map(|l| l.auto_defer.as_deref())
+            .filter_map(|d| serde_json::from_str::<AutoDefer>(d).ok())
+            .find(|d| d.applies_to(&interaction.kind));
+        let deferred = match auto_defer {
+            Some(d) => interaction.defer(d.ephemeral).await,
+            None => false,
+        };
+
+        self.enqueue(
+            &listeners,
+            &format!("interaction/{}", interaction.id.0),
+            outbox::sequence(Some(interaction.id.0)),
+            msg,
+            event_model,
+            Delivery::Interaction { deferred },
+        )
+        .await;
+    }
+
+    /// The listeners of the flows the event is forwarded to.
+    ///
+    /// The flows listening to all channels of a provided bot receive every
+    /// event of that bot.
+    async fn listeners(
+        &self,
+        channel_id: &Option<String>,
+        guild_id: &Option<String>,
+        event_model: &str,
+        info: &EventInfo<'_>,
+    ) -> Vec<FlowListener> {
+        // A flow listening to a channel and its guild only gets the event once
+        let listeners: Option<Vec<FlowListener>> = if self.fingerprint == DEFAULT_BOT_PLACEHOLDER {
             let select = "
-                SELECT flows_user, flow_id
+                SELECT DISTINCT flows_user, flow_id, event_filter, auto_defer
                 FROM listener
-                WHERE channel_id = $1 and bot_token = $2
+                WHERE (channel_id = $1 or guild_id = $2) and bot_fingerprint = $3
             ";
             sqlx::query_as(select)
-                .bind(channel_id.as_u64().to_string())
+                .bind(channel_id)
+                .bind(guild_id)
                 .bind(DEFAULT_BOT_PLACEHOLDER)
                 .fetch_all(&*self.pool)
                 .await
                 .ok()
         } else {
             let select = "
-                SELECT flows_user, flow_id
+                SELECT DISTINCT flows_user, flow_id, event_filter, auto_defer
                 FROM listener
-                WHERE (channel_id = '' or channel_id = $1) and bot_token = $2
+                WHERE ((channel_id = '' and guild_id = '') or channel_id = $1 or guild_id = $2)
+                    and bot_fingerprint = $3
             ";
             sqlx::query_as(select)
-                .bind(channel_id.as_u64().to_string())
-                .bind(self.token.clone())
+                .bind(channel_id)
+                .bind(guild_id)
+                .bind(&self.fingerprint)
                 .fetch_all(&*self.pool)
                 .await
                 .ok()
         };
 
+        listeners
+            .unwrap_or_default()
+            .into_iter()
+            .filter(
+                |l| match l.event_filter.as_deref().map(EventFilter::cached) {
+                    None => true,
+                    Some(Ok(filter)) => filter.matches(event_model, info),
+                    Some(Err(e)) => {
+                        log::error!(
+                            "Dropping a {} event for flow {}, its filter is invalid: {}",
+                            event_model,
+                            l.flow_id,
+                            e
+                        );
+                        false
+                    }
+                },
+            )
+            .collect()
+    }
+
+    async fn enqueue<T: Serialize + ?Sized>(
+        &self,
+        listeners: &[FlowListener],
+        ordering_key: &str,
+        seq: i64,
+        msg: &T,
+        event_model: &str,
+        delivery: Delivery,
+    ) {
+        let flows: Vec<Flow> = listeners
+            .iter()
+            .map(|l| Flow {
+                flows_user: l.flows_user.clone(),
+                flow_id: l.flow_id.clone(),
+            })
+            .collect();
+
         let flows = match flows {
-            Some(vf) if vf.len() > 0 => serde_json::to_string(&vf).unwrap(),
+            vf if !vf.is_empty() => serde_json::to_string(&vf).unwrap(),
             _ => return,
         };
 
-        let client = get_client();
-        _ = client
-            .post(HOOK_URL.as_str())
-            .json(msg)
-            .header("X-Discord-flows", flows)
-            .header("X-Discord-event-model", event_model)
-            .send()
-            .await;
+        let body = match serde_json::to_string(msg) {
+            Ok(b) => b,
+            Err(_) => return,
+        };
+        if let Err(e) = outbox::enqueue(
+            &self.pool,
+            ordering_key,
+            seq,
+            event_model,
+            &flows,
+            &body,
+            delivery,
+        )
+        .await
+        {
+            log::error!("Failed to enqueue {} event: {}", event_model, e);
+        }
     }
 }
@@ -0,0 +1,242 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use serenity::model::gateway::GatewayIntents;
+use sqlx::PgPool;
+
+use crate::{filter::EventFilter, model::ListenerFilter, utils::http::application_flags};
+
+/// The flags of an application allowing its bots to receive the privileged
+/// intents, the limited ones being granted to the unverified bots in less
+/// than 100 servers.
+const GATEWAY_PRESENCE: u64 = 1 << 12 | 1 << 13;
+const GATEWAY_GUILD_MEMBERS: u64 = 1 << 14 | 1 << 15;
+const GATEWAY_MESSAGE_CONTENT: u64 = 1 << 18 | 1 << 19;
+
+/// The privileged intents, with their names in the Discord Developer Portal.
+pub const PRIVILEGED: [(GatewayIntents, &str); 3] = [
+    (GatewayIntents::MESSAGE_CONTENT, "Message Content"),
+    (GatewayIntents::GUILD_MEMBERS, "Server Members"),
+    (GatewayIntents::GUILD_PRESENCES, "Presence"),
+];
+
+/// How many times the application of a bot is read before connecting it,
+/// the delay between two reads doubling from a second.
+const APPLICATION_READS: u32 = 3;
+
+/// The `X-Discord-event-model`s the integration forwards which need intents.
+const EVENT_MODELS: &[&str] = &[
+    "Message",
+    "MessageUpdate",
+    "MessageDelete",
+    "ReactionAdd",
+    "ReactionRemove",
+    "GuildMemberAddition",
+    "GuildMemberRemoval",
+    "VoiceStateUpdate",
+    "GuildScheduledEventCreate",
+    "GuildScheduledEventUpdate",
+    "GuildScheduledEventDelete",
+];
+
+/// The intents needed to receive the events of an `X-Discord-event-model`.
+///
+/// The interactions are received without any intent, and the threads with
+/// [`GatewayIntents::GUILDS`] which every bot connects with.
+pub fn for_event_model(event_model: &str) -> GatewayIntents {
+    match event_model {
+        "Message" | "MessageUpdate" | "MessageDelete" => {
+            GatewayIntents::GUILD_MESSAGES
+                | GatewayIntents::DIRECT_MESSAGES
+                | GatewayIntents::MESSAGE_CONTENT
+        }
+        "ReactionAdd" | "ReactionRemove" => {
+            GatewayIntents::GUILD_MESSAGE_REACTIONS | GatewayIntents::DIRECT_MESSAGE_REACTIONS
+        }
+        "GuildMemberAddition" | "GuildMemberRemoval" => GatewayIntents::GUILD_MEMBERS,
+        "VoiceStateUpdate" => GatewayIntents::GUILD_VOICE_STATES,
+        "GuildScheduledEventCreate" | "GuildScheduledEventUpdate" | "GuildScheduledEventDelete" => {
+            GatewayIntents::GUILD_SCHEDULED_EVENTS
+        }
+        _ => GatewayIntents::empty(),
+    }
+}
+
+/// The intents needed by a listener, to receive the events of the models of
+/// its filter or, without any, all the events the integration forwards.
+pub fn for_filter(filter: Option<&EventFilter>) -> GatewayIntents {
+    match filter {
+        Some(filter) if !filter.event_models.is_empty() => filter
+            .event_models
+            .iter()
+            .fold(GatewayIntents::GUILDS, |intents, m| {
+                intents | for_event_model(m)
+            }),
+        _ => EVENT_MODELS
+            .iter()
+            .fold(GatewayIntents::GUILDS, |intents, m| {
+                intents | for_event_model(m)
+            }),
+    }
+}
+
+/// The intents the bot of `token` may connect with, the privileged ones
+/// only once enabled on its application.
+///
+/// `None` when the application could not be read.
+pub async fn allowed(token: &str) -> Option<GatewayIntents> {
+    let flags = application_flags(token).await?;
+
+    let mut intents = GatewayIntents::non_privileged();
+    if flags & GATEWAY_PRESENCE != 0 {
+        intents |= GatewayIntents::GUILD_PRESENCES;
+    }
+    if flags & GATEWAY_GUILD_MEMBERS != 0 {
+        intents |= GatewayIntents::GUILD_MEMBERS;
+    }
+    if flags & GATEWAY_MESSAGE_CONTENT != 0 {
+        intents |= GatewayIntents::MESSAGE_CONTENT;
+    }
+
+    Some(intents)
+}
+
+/// The intents needed by the listeners of the bot.
+///
+/// The listeners without event models would receive all the events.
+pub async fn needed(bot_fingerprint: &str, pool: &PgPool) -> GatewayIntents {
+    let select = "
+        SELECT event_filter
+        FROM listener
+        WHERE bot_fingerprint = $1
+    ";
+    let listeners: Vec<ListenerFilter> = sqlx::query_as(select)
+        .bind(bot_fingerprint)
+        .fetch_all(pool)
+        .await
+        .unwrap_or_default();
+
+    listeners.iter().fold(GatewayIntents::GUILDS, |intents, l| {
+        let filter = l
+            .event_filter
+            .as_ref()
+            .and_then(|f| serde_json::from_str::<EventFilter>(f).ok());
+        intents | for_filter(filter.as_ref())
+    })
+}
+
+/// The intents the bot connects with: the `needed` ones that its
+/// application allows.
+///
+/// The privileged intents are only kept when the application allows them,
+/// so that a bot without them can still connect. When the application can't
+/// be read, the bot connects with all the `needed` ones rather than losing
+/// the privileged ones, its client stopping with the error recorded if they
+/// turn out not to be allowed.
+pub async fn required(
+    token: &str,
+    bot_fingerprint: &str,
+    needed: GatewayIntents,
+) -> GatewayIntents {
+    for attempt in 0..APPLICATION_READS {
+        if attempt > 0 {
+            tokio::time::sleep(Duration::from_secs(1 << (attempt - 1))).await;
+        }
+        if let Some(allowed) = allowed(token).await {
+            return needed & allowed;
+        }
+    }
+
+    log::error!(
+        "Failed to read the application of bot {}, connecting with the intents {:?} its \
+        listeners need",
+        bot_fingerprint,
+        needed
+    );
+    needed
+}
+
+/// Check the application of the bot of `token` allows the events the
+/// `filter` asks for, reporting all the privileged intents it lacks.
+///
+/// The message events are received without the Message Content intent,
+/// only with an empty content, so only the events missing entirely are an
+/// error, the missing Message Content intent being left to the
+/// [diagnostics](crate::diagnostics).
+pub async fn check(token: &str, filter: Option<&EventFilter>) -> Result<(), (StatusCode, String)> {
+    let filter = match filter {
+        Some(filter) if !filter.event_models.is_empty() => filter,
+        _ => return Ok(()),
+    };
+    let allowed = allowed(token).await.ok_or((
+        StatusCode::BAD_GATEWAY,
+        String::from("Failed to read the application of the bot to check its intents"),
+    ))?;
+
+    let missing = lacking(filter, allowed);
+    match missing.is_empty() {
+        true => Ok(()),
+        false => Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "The bot lacks {}, enable them on the Bot page of the application in the \
+                Discord Developer Portal",
+                missing.join(" and ")
+            ),
+        )),
+    }
+}
+
+/// The privileged intents but Message Content that the events of the
+/// `filter` need and the application doesn't allow, with those events.
+fn lacking(filter: &EventFilter, allowed: GatewayIntents) -> Vec<String> {
+    let mut missing = vec![];
+    for (intent, name) in PRIVILEGED {
+        if intent == GatewayIntents::MESSAGE_CONTENT || allowed.contains(intent) {
+            continue;
+        }
+        let event_models: Vec<&str> = filter
+            .event_models
+            .iter()
+            .filter(|m| for_event_model(m).contains(intent))
+            .map(String::as_str)
+            .collect();
+        if !event_models.is_empty() {
+            missing.push(format!(
+                "the privileged {} intent, needed by the `{}` events",
+                name,
+                event_models.join("`, `")
+            ));
+        }
+    }
+
+    missing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(event_models: &[&str]) -> EventFilter {
+        EventFilter {
+            event_models: event_models.iter().map(|m| m.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reports_every_missing_privileged_intent() {
+        let filter = filter(&["Message", "GuildMemberAddition", "GuildMemberRemoval"]);
+
+        let missing = lacking(&filter, GatewayIntents::non_privileged());
+
+        assert_eq!(
+            missing,
+            [
+                "the privileged Server Members intent, needed by the `GuildMemberAddition`, \
+            `GuildMemberRemoval` events"
+            ]
+        );
+        assert!(lacking(&filter, GatewayIntents::all()).is_empty());
+    }
+}
@@ -1,4 +1,6 @@
-use crate::route::{access, auth, connected, listen, proxy, static_path};
+use crate::route::{
+    access, auth, connected, listen, listen_to_channel, proxy, static_path, status, unlisten,
+};
 
 use std::sync::Arc;
 
@@ -7,21 +9,42 @@ use axum::{
     Router,
 };
 use include_dir::{include_dir, Dir};
-use serenity::model::gateway::GatewayIntents;
 use sqlx::{Executor, PgPool};
 use state::AppState;
 
+mod defer;
+mod diagnostics;
+mod filter;
 mod handler;
+mod intents;
 mod model;
+mod outbox;
 mod route;
 mod shared;
 mod state;
+mod supervisor;
 mod utils;
 
 lazy_static::lazy_static! {
     static ref HOOK_URL: String =
         std::env::var("PLATFORM_HOOK_URL").unwrap_or(String::from("https://code.flows.network/hook/discord/message"));
     static ref DEFAULT_TOKEN: String = std::env::var("DEFAULT_DISCORD_APP_BOT_TOKEN").unwrap();
+    /// The key the bot tokens are encrypted with at rest, 32 bytes hex
+    /// encoded (`openssl rand -hex 32`), required to deploy.
+    ///
+    /// On the first start with it, the tokens still stored in plain text are
+    /// encrypted. It must not change afterwards: the tokens encrypted with
+    /// another key can't be read, and their flows have to register again.
+    static ref TOKEN_ENCRYPTION_KEY: String =
+        std::env::var("TOKEN_ENCRYPTION_KEY").expect("env `TOKEN_ENCRYPTION_KEY` not present");
+    /// Whether the legacy routes, still called by the SDK versions which
+    /// don't send the flows JWT, refuse the calls without it.
+    ///
+    /// Off during the deprecation window; to be set to `true` once the flows
+    /// built with those versions have been rebuilt. The calls on behalf of
+    /// the default bot require it regardless.
+    static ref FLOWS_JWT_ENFORCED: bool =
+        std::env::var("FLOWS_JWT_ENFORCED").map(|v| v == "true").unwrap_or(false);
 }
 static STATIC_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/static");
 
@@ -32,12 +55,20 @@ async fn main() {
     let state = init().await;
 
     let app = Router::new()
-        .route("/:flows_user/:flow_id/:channel_id/listen", post(listen))
+        .route(
+            "/:flows_user/:flow_id/listen",
+            post(listen).delete(unlisten),
+        )
+        .route(
+            "/:flows_user/:flow_id/:channel_id/listen",
+            post(listen_to_channel),
+        )
         .route("/proxy/:api/*path", any(proxy))
         .route("/connected/:flows_user", get(connected))
         .route("/static/*path", get(static_path))
         .route("/access/:state", get(access))
         .route("/auth", get(auth))
+        .route("/status", get(status))
         .with_state(state);
 
     axum::Server::bind(&"0.0.0.0:6870".parse().unwrap())
@@ -54,6 +85,9 @@ async fn init() -> AppState {
     let pool = Arc::new(PgPool::connect(&db_url).await.unwrap());
 
     _ = pool.execute(include_str!("../schema.sql")).await.unwrap();
+    utils::database::encrypt_plain_tokens(&pool).await.unwrap();
+
+    tokio::spawn(outbox::run(pool.clone()));
 
     let state = AppState { pool };
 
@@ -1,4 +1,10 @@
 use serde::{Deserialize, Serialize};
+use serenity::model::{
+    id::{ChannelId, GuildId, MessageId},
+    user::User,
+};
+
+use crate::{defer::AutoDefer, diagnostics::Diagnostic, filter::EventFilter};
 
 #[derive(Serialize, Deserialize, sqlx::FromRow)]
 pub struct Flow {
@@ -6,6 +12,51 @@ pub struct Flow {
     pub flow_id: String,
 }
 
+#[derive(sqlx::FromRow)]
+pub struct FlowListener {
+    pub flows_user: String,
+    pub flow_id: String,
+    pub event_filter: Option<String>,
+    pub auto_defer: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct MessageDelete {
+    pub guild_id: Option<GuildId>,
+    pub channel_id: ChannelId,
+    #[serde(rename = "id")]
+    pub message_id: MessageId,
+}
+
+#[derive(Serialize)]
+pub struct GuildMemberRemoval {
+    pub guild_id: GuildId,
+    pub user: User,
+}
+
+#[derive(Deserialize)]
+pub struct FlowPath {
+    pub flows_user: String,
+    pub flow_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct ListenBody {
+    #[serde(default)]
+    pub channel_ids: Vec<u64>,
+    #[serde(default)]
+    pub guild_ids: Vec<u64>,
+    pub filter: Option<EventFilter>,
+    pub auto_defer: Option<AutoDefer>,
+}
+
+/// The answer to a registration, with what may keep the bot from receiving
+/// the events of the flow.
+#[derive(Serialize)]
+pub struct ListenResponse {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
 #[derive(Serialize, Deserialize, sqlx::FromRow)]
 pub struct ListenPath {
     pub flows_user: String,
@@ -19,6 +70,49 @@ pub struct Bot {
     pub token: String,
 }
 
+#[derive(sqlx::FromRow)]
+pub struct ListenerFilter {
+    pub event_filter: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct BotFingerprint {
+    #[sqlx(rename = "bot_fingerprint")]
+    pub fingerprint: String,
+}
+
+/// The answer of `GET /gateway/bot`.
+#[derive(Debug, Deserialize)]
+pub struct GatewayBot {
+    /// The recommended number of shards.
+    pub shards: u64,
+    pub session_start_limit: SessionStartLimit,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionStartLimit {
+    pub remaining: u64,
+    /// The milliseconds until the limit resets.
+    pub reset_after: u64,
+    /// How many shards may identify at the same time.
+    #[serde(default = "one")]
+    pub max_concurrency: u64,
+}
+
+fn one() -> u64 {
+    1
+}
+
+#[derive(sqlx::FromRow)]
+pub struct OutboxEvent {
+    pub id: i64,
+    pub event_model: String,
+    pub flows: String,
+    pub body: String,
+    pub deferred: bool,
+    pub attempts: i32,
+}
+
 #[derive(sqlx::FromRow)]
 pub struct Count {
     pub count: i64,
@@ -32,9 +126,11 @@ pub struct DiscordChannel {
     pub owner_id: Option<String>,
 }
 
+/// The query string of the legacy listen route, only read to refuse the bot
+/// token sent in it.
 #[derive(Deserialize)]
 pub struct ListenerQuery {
-    pub bot_token: String,
+    pub bot_token: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -70,6 +166,17 @@ pub struct AuthStateClaim {
     exp: usize,
 }
 
+/// The claim of the JWT the flows platform signs for the calls it makes on
+/// behalf of a flows user, optionally restricted to one of their flows.
+///
+/// The expiration is checked while decoding.
+#[derive(Debug, Deserialize)]
+pub struct FlowsClaim {
+    pub flows_user: String,
+    #[serde(default)]
+    pub flow_id: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UserResponse {
     pub id: String,
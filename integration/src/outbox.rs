@@ -0,0 +1,520 @@
+use std::{
+    collections::HashSet,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use once_cell::sync::OnceCell;
+use reqwest::StatusCode;
+use sqlx::PgPool;
+use tokio::sync::Notify;
+
+use crate::{
+    model::OutboxEvent,
+    shared::{get_client, REQUEST_TIMEOUT},
+    HOOK_URL,
+};
+
+/// Attempts after which an event is moved to the dead-letter table.
+const MAX_ATTEMPTS: i32 = 10;
+/// The delay before the first retry, doubled on every failure.
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(600);
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// Serenity dispatches every event in its own task, so an event is only
+/// delivered once it has been in the outbox long enough for the events that
+/// raced with it to be enqueued too.
+///
+/// The interactions, which have to be answered within 3 seconds, are
+/// delivered right away.
+const SETTLE_DELAY_MS: i64 = 500;
+/// How long an event being delivered is leased to the replica delivering it,
+/// longer than the request to the platform can take.
+const LEASE: Duration = REQUEST_TIMEOUT.saturating_mul(2);
+
+/// Milliseconds between the Unix epoch and the first second of 2015.
+const DISCORD_EPOCH: u64 = 1_420_070_400_000;
+
+/// The position of an event within its channel, the snowflake of the event
+/// when it has one, else a snowflake of the time it was received.
+pub fn sequence(snowflake: Option<u64>) -> i64 {
+    let snowflake = snowflake.unwrap_or_else(|| {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        now.saturating_sub(DISCORD_EPOCH) << 22
+    });
+
+    snowflake as i64
+}
+
+/// When an event is delivered to the platform.
+#[derive(Clone, Copy)]
+pub enum Delivery {
+    /// Once the events racing with it are enqueued too.
+    Settled,
+    /// Right away, `deferred` telling the integration already responded to
+    /// the interaction.
+    Interaction { deferred: bool },
+}
+
+pub async fn enqueue(
+    pool: &PgPool,
+    ordering_key: &str,
+    seq: i64,
+    event_model: &str,
+    flows: &str,
+    body: &str,
+    delivery: Delivery,
+) -> Result<(), String> {
+    let (deferred, immediate) = match delivery {
+        Delivery::Settled => (false, false),
+        Delivery::Interaction { deferred } => (deferred, true),
+    };
+    let insert = "
+        INSERT INTO hook_outbox(ordering_key, seq, event_model, flows, body, deferred, immediate)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+    ";
+    sqlx::query(insert)
+        .bind(ordering_key)
+        .bind(seq)
+        .bind(event_model)
+        .bind(flows)
+        .bind(body)
+        .bind(deferred)
+        .bind(immediate)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if immediate {
+        wakeup().notify_one();
+    }
+
+    Ok(())
+}
+
+/// Woken to deliver the immediate events without waiting for the next poll.
+fn wakeup() -> &'static Notify {
+    static INSTANCE: OnceCell<Notify> = OnceCell::new();
+    INSTANCE.get_or_init(Notify::new)
+}
+
+type InFlight = Arc<Mutex<HashSet<String>>>;
+
+/// An ordering key being delivered, released when its worker ends, even on a
+/// panic.
+struct Worker {
+    in_flight: InFlight,
+    ordering_key: String,
+}
+
+impl Worker {
+    /// Start a worker for the key, unless one is already delivering it.
+    fn start(in_flight: &InFlight, ordering_key: String) -> Option<Self> {
+        match in_flight.lock().unwrap().insert(ordering_key.clone()) {
+            true => Some(Worker {
+                in_flight: in_flight.clone(),
+                ordering_key,
+            }),
+            false => None,
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        if let Ok(mut in_flight) = self.in_flight.lock() {
+            in_flight.remove(&self.ordering_key);
+        }
+    }
+}
+
+/// Deliver the events of the outbox forever.
+///
+/// The channels are delivered concurrently, each by its own worker, but the
+/// events of a channel one after another: a channel is blocked by its oldest
+/// event until it is delivered or moved to the dead-letter table.
+///
+/// The outbox keeps being polled while the workers run, the channels which
+/// already have one are skipped. The head event of a channel is leased to
+/// the replica delivering it, so that the other replicas leave the channel
+/// alone meanwhile.
+pub async fn run(pool: Arc<PgPool>) {
+    let in_flight = InFlight::default();
+
+    loop {
+        let keys = due_keys(&pool).await.unwrap_or_else(|e| {
+            log::error!("Failed to read the outbox: {}", e);
+            vec![]
+        });
+
+        for worker in keys
+            .into_iter()
+            .filter_map(|key| Worker::start(&in_flight, key))
+        {
+            let pool = pool.clone();
+            tokio::spawn(async move { deliver_channel(&pool, &worker.ordering_key).await });
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            _ = wakeup().notified() => {}
+        }
+    }
+}
+
+/// The ordering keys whose oldest event is due and not leased.
+async fn due_keys(pool: &PgPool) -> Result<Vec<String>, String> {
+    let select = "
+        SELECT ordering_key FROM (
+            SELECT DISTINCT ON (ordering_key)
+                ordering_key, next_attempt_at, created_at, immediate, leased_until
+            FROM hook_outbox
+            ORDER BY ordering_key, seq, id
+        ) heads
+        WHERE next_attempt_at <= now()
+            AND (immediate
+                OR created_at <= now() - make_interval(secs => $1::double precision / 1000))
+            AND (leased_until IS NULL OR leased_until < now())
+    ";
+    sqlx::query_scalar(select)
+        .bind(SETTLE_DELAY_MS)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+async fn deliver_channel(pool: &PgPool, ordering_key: &str) {
+    if let Err(e) = try_deliver_channel(pool, ordering_key, post).await {
+        log::error!("Failed to deliver the outbox of `{}`: {}", ordering_key, e);
+    }
+}
+
+async fn try_deliver_channel<F, Fut>(
+    pool: &PgPool,
+    ordering_key: &str,
+    post: F,
+) -> Result<(), String>
+where
+    F: Fn(OutboxEvent) -> Fut,
+    Fut: Future<Output = Result<(), (bool, String)>>,
+{
+    loop {
+        let event = match lease_head(pool, ordering_key).await? {
+            Some(e) => e,
+            None => return Ok(()),
+        };
+
+        let id = event.id;
+        let attempts = event.attempts + 1;
+        match post(event).await {
+            Ok(()) => delete(pool, id).await?,
+            Err((retryable, error)) if retryable && attempts < MAX_ATTEMPTS => {
+                // Later events of the channel wait for this one
+                return retry_later(pool, id, &error, backoff(attempts)).await;
+            }
+            Err((_, error)) => dead_letter(pool, id, &error).await?,
+        }
+    }
+}
+
+/// Lease the oldest event of the channel if it is due and not leased
+/// already.
+///
+/// The head is locked while it is leased, so of the replicas racing for it
+/// only one gets it. The later events of the channel are never leased before
+/// the head is gone, which keeps them in order.
+async fn lease_head(pool: &PgPool, ordering_key: &str) -> Result<Option<OutboxEvent>, String> {
+    let lease = "
+        UPDATE hook_outbox SET leased_until = now() + make_interval(secs => $3)
+        WHERE id = (
+            SELECT id FROM hook_outbox
+            WHERE ordering_key = $1
+            ORDER BY seq, id
+            LIMIT 1
+            FOR UPDATE
+        )
+            AND next_attempt_at <= now()
+            AND (immediate
+                OR created_at <= now() - make_interval(secs => $2::double precision / 1000))
+            AND (leased_until IS NULL OR leased_until < now())
+        RETURNING id, event_model, flows, body, deferred, attempts
+    ";
+    sqlx::query_as(lease)
+        .bind(ordering_key)
+        .bind(SETTLE_DELAY_MS)
+        .bind(LEASE.as_secs_f64())
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Post the event to the platform, the error tells whether it is worth
+/// retrying.
+async fn post(event: OutboxEvent) -> Result<(), (bool, String)> {
+    let mut req = get_client()
+        .post(HOOK_URL.as_str())
+        .header("Content-Type", "application/json")
+        .header("X-Discord-flows", event.flows)
+        .header("X-Discord-event-model", event.event_model);
+    // Tells the flow the integration already responded to the interaction
+    if event.deferred {
+        req = req.header("X-Discord-deferred", "true");
+    }
+    let resp = req.body(event.body).send().await;
+
+    match resp {
+        Ok(r) if r.status().is_success() => Ok(()),
+        Ok(r) => {
+            let status = r.status();
+            let retryable = status.is_server_error()
+                || status == StatusCode::TOO_MANY_REQUESTS
+                || status == StatusCode::REQUEST_TIMEOUT;
+            Err((retryable, status.to_string()))
+        }
+        Err(e) => Err((true, e.to_string())),
+    }
+}
+
+fn backoff(attempts: i32) -> Duration {
+    BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempts as u32 - 1))
+        .min(MAX_BACKOFF)
+}
+
+async fn delete(pool: &PgPool, id: i64) -> Result<(), String> {
+    sqlx::query("DELETE FROM hook_outbox WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+async fn retry_later(pool: &PgPool, id: i64, error: &str, delay: Duration) -> Result<(), String> {
+    let update = "
+        UPDATE hook_outbox
+        SET attempts = attempts + 1, last_error = $2, leased_until = NULL,
+            next_attempt_at = now() + make_interval(secs => $3)
+        WHERE id = $1
+    ";
+    sqlx::query(update)
+        .bind(id)
+        .bind(error)
+        .bind(delay.as_secs_f64())
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+async fn dead_letter(pool: &PgPool, id: i64, error: &str) -> Result<(), String> {
+    let moved = "
+        WITH moved AS (
+            DELETE FROM hook_outbox WHERE id = $1 RETURNING *
+        )
+        INSERT INTO hook_dead_letter(
+            id, ordering_key, seq, event_model, flows, body, deferred, attempts, last_error,
+            created_at
+        )
+        SELECT id, ordering_key, seq, event_model, flows, body, deferred, attempts + 1, $2,
+            created_at
+        FROM moved
+    ";
+    sqlx::query(moved)
+        .bind(id)
+        .bind(error)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    log::warn!("Moved event {} to the dead-letter table: {}", id, error);
+
+    Ok(())
+}
+
+// The `sqlx::test`s each run in a fresh database of the `DATABASE_URL` server
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use sqlx::Executor;
+
+    use super::*;
+
+    async fn setup(pool: &PgPool, events: &[(&str, i64)]) {
+        pool.execute(include_str!("../schema.sql")).await.unwrap();
+        for (ordering_key, seq) in events {
+            let body = seq.to_string();
+            enqueue(
+                pool,
+                ordering_key,
+                *seq,
+                "Message",
+                "[]",
+                &body,
+                Delivery::Settled,
+            )
+            .await
+            .unwrap();
+        }
+        // Past the settle delay
+        sqlx::query("UPDATE hook_outbox SET created_at = now() - interval '1 minute'")
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    /// Deliver the channel, the bodies of the posted events are recorded and
+    /// the posts fail with the given errors, in order, until there is none
+    /// left.
+    async fn deliver(pool: &PgPool, ordering_key: &str, errors: Vec<(bool, &str)>) -> Vec<String> {
+        let posted = Mutex::new(vec![]);
+        let errors = Mutex::new(errors.into_iter());
+        try_deliver_channel(pool, ordering_key, |event| {
+            posted.lock().unwrap().push(event.body);
+            let result = match errors.lock().unwrap().next() {
+                Some((retryable, e)) => Err((retryable, e.to_string())),
+                None => Ok(()),
+            };
+            async move { result }
+        })
+        .await
+        .unwrap();
+
+        posted.into_inner().unwrap()
+    }
+
+    async fn outbox(pool: &PgPool) -> Vec<(String, i32)> {
+        sqlx::query_as("SELECT body, attempts FROM hook_outbox ORDER BY seq")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn dead_letters(pool: &PgPool) -> Vec<(String, String)> {
+        sqlx::query_as("SELECT body, last_error FROM hook_dead_letter ORDER BY seq")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn delivers_the_events_of_a_channel_in_order(pool: PgPool) {
+        setup(&pool, &[("1", 3), ("1", 1), ("2", 4), ("1", 2)]).await;
+
+        assert_eq!(deliver(&pool, "1", vec![]).await, ["1", "2", "3"]);
+        assert_eq!(outbox(&pool).await, [(String::from("4"), 0)]);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn blocks_the_channel_until_the_retry(pool: PgPool) {
+        setup(&pool, &[("1", 1), ("1", 2)]).await;
+
+        let posted = deliver(&pool, "1", vec![(true, "502 Bad Gateway")]).await;
+        assert_eq!(posted, ["1"]);
+        assert_eq!(
+            outbox(&pool).await,
+            [(String::from("1"), 1), (String::from("2"), 0)]
+        );
+
+        // Neither the failed event nor the next one is due before the backoff
+        assert!(deliver(&pool, "1", vec![]).await.is_empty());
+        assert!(due_keys(&pool).await.unwrap().is_empty());
+
+        sqlx::query("UPDATE hook_outbox SET next_attempt_at = now()")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(deliver(&pool, "1", vec![]).await, ["1", "2"]);
+        assert!(outbox(&pool).await.is_empty());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn dead_letters_the_events_not_worth_retrying(pool: PgPool) {
+        setup(&pool, &[("1", 1), ("1", 2)]).await;
+
+        let posted = deliver(&pool, "1", vec![(false, "400 Bad Request")]).await;
+        assert_eq!(posted, ["1", "2"]);
+        assert!(outbox(&pool).await.is_empty());
+        assert_eq!(
+            dead_letters(&pool).await,
+            [(String::from("1"), String::from("400 Bad Request"))]
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn dead_letters_the_events_out_of_attempts(pool: PgPool) {
+        setup(&pool, &[("1", 1)]).await;
+        sqlx::query("UPDATE hook_outbox SET attempts = $1")
+            .bind(MAX_ATTEMPTS - 1)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        deliver(&pool, "1", vec![(true, "503 Service Unavailable")]).await;
+        assert!(outbox(&pool).await.is_empty());
+        assert_eq!(
+            dead_letters(&pool).await,
+            [(String::from("1"), String::from("503 Service Unavailable"))]
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn leases_the_head_to_a_single_replica(pool: PgPool) {
+        setup(&pool, &[("1", 1), ("1", 2)]).await;
+
+        let head = lease_head(&pool, "1").await.unwrap().unwrap();
+        assert_eq!(head.body, "1");
+        // Neither the head nor the event behind it goes to another replica
+        assert!(lease_head(&pool, "1").await.unwrap().is_none());
+        assert!(due_keys(&pool).await.unwrap().is_empty());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn delivers_the_interactions_right_away(pool: PgPool) {
+        pool.execute(include_str!("../schema.sql")).await.unwrap();
+        enqueue(&pool, "1", 1, "Message", "[]", "1", Delivery::Settled)
+            .await
+            .unwrap();
+        let interaction = Delivery::Interaction { deferred: false };
+        enqueue(
+            &pool,
+            "interaction/2",
+            2,
+            "Interaction",
+            "[]",
+            "2",
+            interaction,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(due_keys(&pool).await.unwrap(), ["interaction/2"]);
+    }
+
+    #[test]
+    fn backs_off_exponentially() {
+        assert_eq!(backoff(1), Duration::from_secs(1));
+        assert_eq!(backoff(4), Duration::from_secs(8));
+        assert_eq!(backoff(MAX_ATTEMPTS), Duration::from_secs(512));
+        assert_eq!(backoff(20), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn skips_the_keys_being_delivered() {
+        let in_flight = InFlight::default();
+
+        let worker = Worker::start(&in_flight, String::from("1")).unwrap();
+        assert!(Worker::start(&in_flight, String::from("1")).is_none());
+        assert!(Worker::start(&in_flight, String::from("2")).is_some());
+
+        drop(worker);
+        assert!(Worker::start(&in_flight, String::from("1")).is_some());
+    }
+}
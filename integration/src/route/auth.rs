@@ -2,34 +2,20 @@ use axum::{
     extract::{Query, State},
     response::Redirect,
 };
-use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
 
 use crate::{
     model::{AuthQuery, AuthStateClaim, AuthTokenRequest, AuthTokenResponse, UserResponse},
     shared::get_client,
     state::AppState,
+    utils::jwt::decode_claim,
 };
 
 pub async fn auth(
     State(global_state): State<AppState>,
     Query(AuthQuery { state, code }): Query<AuthQuery>,
 ) -> Result<Redirect, String> {
-    let mut val = Validation::new(Algorithm::RS256);
-    // Set the time skew
-    val.leeway = 60;
-    let public_key = std::env::var("FLOWS_JWT_PUBLIC_KEY").unwrap();
-
     // Decode the flows_user from jwt
-    let state_claim = match decode::<AuthStateClaim>(
-        &state,
-        &DecodingKey::from_rsa_pem(public_key.as_bytes()).unwrap(),
-        &val,
-    ) {
-        Ok(c) => c,
-        Err(e) => {
-            return Err(e.to_string());
-        }
-    };
+    let state_claim: AuthStateClaim = decode_claim(&state)?;
 
     let token_resp = auth_token(code).await?;
 
@@ -45,7 +31,7 @@ pub async fn auth(
         discord_email = excluded.discord_email
     ";
     _ = sqlx::query(insert)
-        .bind(state_claim.claims.flows_user)
+        .bind(state_claim.flows_user)
         .bind(token_resp.guild.id)
         .bind(token_resp.guild.name)
         .bind(user_resp.id)
@@ -1,21 +1,45 @@
-use axum::extract::{Path, Query, State};
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    Json,
+};
 use reqwest::StatusCode;
-use sqlx::{PgPool, Postgres};
 
 use crate::{
-    model::{DiscordChannel, GuildAuthor, ListenPath, ListenerQuery},
-    shared::get_client,
-    state::AppState,
+    defer::AutoDefer,
+    diagnostics::diagnose,
+    filter::EventFilter,
+    intents,
+    model::{
+        BotFingerprint, FlowPath, FlowsClaim, ListenBody, ListenPath, ListenResponse, ListenerQuery,
+    },
+    state::{real_token, AppState},
     utils::{
-        database::{del_listener_by_token, safe_shutdown},
+        crypto::fingerprint,
+        database::{del_listener_by_token, del_listeners, safe_shutdown},
         http::check_token,
+        jwt::LegacyClaim,
+        permission::{authorized_channel, authorized_guild},
     },
-    DEFAULT_BOT_PLACEHOLDER, DEFAULT_TOKEN,
+    DEFAULT_BOT_PLACEHOLDER,
 };
 
 const NONE_CHANNEL_ID: &'static str = "0";
 
-pub async fn listen(
+/// The header carrying the token of the bot to listen to, so that it never
+/// shows up in a URL.
+const BOT_TOKEN_HEADER: &str = "X-Discord-Bot-Token";
+
+/// Register a flow to a single channel, `0` meaning all the channels of the
+/// bot, with an optional [`EventFilter`] as body. A body which is not a
+/// filter is rejected rather than ignored.
+///
+/// Kept for the flows built with the SDK versions before multiple channels
+/// were supported, which is why the flows JWT is only checked when it is
+/// sent (see [`LegacyClaim`]), but for the default bot. The bot token is
+/// refused in the query string, where it would end up in the access logs.
+pub async fn listen_to_channel(
     Path(ListenPath {
         flows_user,
         flow_id,
@@ -23,179 +47,292 @@ pub async fn listen(
     }): Path<ListenPath>,
     State(state): State<AppState>,
     Query(ListenerQuery { bot_token }): Query<ListenerQuery>,
+    claim: LegacyClaim,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<ListenResponse>, (StatusCode, String)> {
+    if bot_token.is_some() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "The bot token is only accepted in the {} header",
+                BOT_TOKEN_HEADER
+            ),
+        ));
+    }
+    let bot_token = bot_token_from_header(&headers)
+        .ok_or((StatusCode::BAD_REQUEST, String::from("Missing bot token")))?;
+    let claim = match bot_token == DEFAULT_BOT_PLACEHOLDER {
+        true => Some(claim.required()?),
+        false => claim.0,
+    };
+    if let Some(claim) = claim {
+        claim.authorize(&flows_user, &flow_id)?;
+    }
+
+    let filter = match body.iter().all(u8::is_ascii_whitespace) {
+        true => None,
+        false => serde_json::from_slice::<Option<EventFilter>>(&body)
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid filter: {}", e)))?,
+    };
+
+    let channel_ids = match channel_id == NONE_CHANNEL_ID {
+        true => vec![],
+        false => vec![channel_id],
+    };
+
+    let flow = FlowPath {
+        flows_user,
+        flow_id,
+    };
+    register(state, flow, bot_token, channel_ids, vec![], filter, None).await
+}
+
+/// Register a flow to a set of channels and guilds, replacing the ones it
+/// was listening to before.
+pub async fn listen(
+    Path(flow): Path<FlowPath>,
+    State(state): State<AppState>,
+    claim: FlowsClaim,
+    headers: HeaderMap,
+    Json(ListenBody {
+        channel_ids,
+        guild_ids,
+        filter,
+        auto_defer,
+    }): Json<ListenBody>,
+) -> Result<Json<ListenResponse>, (StatusCode, String)> {
+    claim.authorize(&flow.flows_user, &flow.flow_id)?;
+
+    let bot_token = bot_token_from_header(&headers)
+        .ok_or((StatusCode::BAD_REQUEST, String::from("Missing bot token")))?;
+    let channel_ids = channel_ids.iter().map(u64::to_string).collect();
+    let guild_ids = guild_ids.iter().map(u64::to_string).collect();
+
+    register(
+        state,
+        flow,
+        bot_token,
+        channel_ids,
+        guild_ids,
+        filter,
+        auto_defer,
+    )
+    .await
+}
+
+/// Remove all the listeners of a flow, disconnecting the bots no other flow
+/// listens to anymore.
+pub async fn unlisten(
+    Path(FlowPath {
+        flows_user,
+        flow_id,
+    }): Path<FlowPath>,
+    State(state): State<AppState>,
+    claim: FlowsClaim,
 ) -> Result<StatusCode, (StatusCode, String)> {
+    claim.authorize(&flows_user, &flow_id)?;
+
     let pool = &state.pool;
 
+    let old = listener::select_old(&flow_id, &flows_user, pool).await;
+
+    del_listeners(&flow_id, &flows_user, pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    for BotFingerprint { fingerprint } in old {
+        safe_shutdown(&fingerprint, pool).await;
+    }
+
+    Ok(StatusCode::OK)
+}
+
+async fn register(
+    state: AppState,
+    flow: FlowPath,
+    bot_token: String,
+    channel_ids: Vec<String>,
+    guild_ids: Vec<String>,
+    filter: Option<EventFilter>,
+    auto_defer: Option<AutoDefer>,
+) -> Result<Json<ListenResponse>, (StatusCode, String)> {
+    let pool = &state.pool;
+
+    let event_filter = filter.as_ref().map(|f| serde_json::to_string(f).unwrap());
+    let auto_defer = auto_defer.map(|d| serde_json::to_string(&d).unwrap());
+
     if bot_token == DEFAULT_BOT_PLACEHOLDER {
-        match channel_id != NONE_CHANNEL_ID {
-            true => match authorized_channel(&flows_user, &channel_id, pool).await? {
-                true => {
-                    listener::insert_listener(
-                        &flow_id,
-                        &flows_user,
-                        &channel_id,
-                        DEFAULT_BOT_PLACEHOLDER,
-                        pool,
-                    )
-                    .await?;
-                    return Ok(StatusCode::OK);
-                }
-                false => {
-                    return Err((
-                        StatusCode::BAD_REQUEST,
-                        String::from("Not authorized channel"),
-                    ));
-                }
-            },
-            false => {
-                return Err((StatusCode::BAD_REQUEST, String::from("Bad request")));
+        // The default bot is on many servers, it can't listen to all of them
+        if channel_ids.is_empty() && guild_ids.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, String::from("Bad request")));
+        }
+
+        for channel_id in channel_ids.iter() {
+            if !authorized_channel(&flow.flows_user, channel_id, pool).await? {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Not authorized channel: {}", channel_id),
+                ));
+            }
+        }
+        for guild_id in guild_ids.iter() {
+            if !authorized_guild(&flow.flows_user, guild_id, pool).await {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Not authorized guild: {}", guild_id),
+                ));
             }
         }
+    } else if !check_token(&bot_token).await {
+        return Err((StatusCode::FORBIDDEN, "Unauthorized token".to_string()));
     }
 
-    let channel_id = match channel_id == NONE_CHANNEL_ID {
-        true => String::new(),
-        false => channel_id,
-    };
+    intents::check(real_token(&bot_token), filter.as_ref()).await?;
 
-    if !check_token(&bot_token).await {
-        return Err((StatusCode::FORBIDDEN, "Unauthorized token".to_string()));
-    }
+    // The default bot is only listening to the channels and guilds it was
+    // authorized on, the custom ones may lack what the flow needs
+    let diagnostics = match bot_token == DEFAULT_BOT_PLACEHOLDER {
+        true => vec![],
+        false => diagnose(&bot_token, &channel_ids, &guild_ids, filter.as_ref()).await,
+    };
 
-    let old = listener::select_old(&flow_id, &flows_user, &channel_id, pool).await;
-    if old.is_some() {
-        if old.as_ref().unwrap().token == bot_token {
-            return Ok(StatusCode::OK);
-        }
-    }
+    let bot_fingerprint = fingerprint(&bot_token);
+    let old = listener::select_old(&flow.flow_id, &flow.flows_user, pool).await;
 
-    listener::insert_listener(&flow_id, &flows_user, &channel_id, &bot_token, pool).await?;
+    listener::replace_listeners(
+        &flow,
+        &channel_ids,
+        &guild_ids,
+        &bot_token,
+        event_filter.as_deref(),
+        auto_defer.as_deref(),
+        pool,
+    )
+    .await?;
 
-    if old.is_some() {
-        safe_shutdown(&old.as_ref().unwrap().token, pool).await;
+    for BotFingerprint { fingerprint } in old {
+        if fingerprint != bot_fingerprint {
+            safe_shutdown(&fingerprint, pool).await;
+        }
     }
 
+    // A bot already running is only reconnected if the listener needs more
+    // intents than it was connected with
+    let FlowPath {
+        flows_user,
+        flow_id,
+    } = flow;
     tokio::spawn(async move {
         let cloned = state.pool.clone();
         _ = state
-            .start_client(bot_token.clone(), |start| async move {
+            .start_client(bot_token, |start| async move {
                 if !start {
-                    _ = del_listener_by_token(&flow_id, &flows_user, "", &bot_token, &cloned).await;
-                    safe_shutdown(&bot_token, &cloned).await;
+                    _ = del_listener_by_token(&flow_id, &flows_user, &bot_fingerprint, &cloned)
+                        .await;
+                    safe_shutdown(&bot_fingerprint, &cloned).await;
                 }
             })
             .await;
     });
 
-    Ok(StatusCode::OK)
+    Ok(Json(ListenResponse { diagnostics }))
 }
 
-async fn authorized_channel(
-    flows_user: &str,
-    channel_id: &str,
-    pool: &PgPool,
-) -> Result<bool, (StatusCode, String)> {
-    let channel = get_channel(&channel_id).await?;
-    let (sql, id) = match channel.guild_id {
-        Some(gid) => (
-            "SELECT * FROM guild_author
-            WHERE flows_user = $1 AND discord_guild_id = $2
-            ",
-            gid,
-        ),
-        None => match channel.owner_id {
-            Some(oid) => (
-                "SELECT * FROM guild_author
-                WHERE flows_user = $1 AND discord_user_id = $2
-                ",
-                oid,
-            ),
-            None => {
-                return Ok(false);
-            }
-        },
-    };
-
-    Ok(sqlx::query_as::<Postgres, GuildAuthor>(sql)
-        .bind(flows_user)
-        .bind(id)
-        .fetch_optional(pool)
-        .await
-        .unwrap_or_default()
-        .is_some())
-}
-
-async fn get_channel(channel_id: &str) -> Result<DiscordChannel, (StatusCode, String)> {
-    let url = format!("https://discord.com/api/channels/{}", channel_id);
-
-    let client = get_client();
-    let resp = client
-        .get(url)
-        .header("Authorization", &format!("Bot {}", &*DEFAULT_TOKEN))
-        .send()
-        .await;
-
-    match resp {
-        Ok(r) => match r.status().is_success() {
-            true => r
-                .json::<DiscordChannel>()
-                .await
-                .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string())),
-            false => Err((r.status(), r.text().await.unwrap_or_else(|e| e.to_string()))),
-        },
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
-    }
+fn bot_token_from_header(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(BOT_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
 }
 
 mod listener {
     use reqwest::StatusCode;
     use sqlx::PgPool;
 
-    use crate::model::Bot;
+    use crate::{
+        model::{BotFingerprint, FlowPath},
+        utils::crypto::{encrypt, fingerprint},
+    };
 
-    pub async fn insert_listener(
-        flow_id: &str,
-        flows_user: &str,
-        channel_id: &str,
+    /// Replace all the listeners of the flow by one per channel and one per
+    /// guild, or a single one listening to all the channels of the bot when
+    /// there are none.
+    pub async fn replace_listeners(
+        flow: &FlowPath,
+        channel_ids: &[String],
+        guild_ids: &[String],
         bot_token: &str,
+        event_filter: Option<&str>,
+        auto_defer: Option<&str>,
         pool: &PgPool,
     ) -> Result<(), (StatusCode, String)> {
-        let insert = "
-            INSERT INTO listener(flow_id, flows_user, channel_id, bot_token)
-            VALUES ($1, $2, $3, $4)
-            ON CONFLICT (flow_id, flows_user)
-            DO UPDATE SET bot_token = excluded.bot_token, channel_id = excluded.channel_id
+        let internal_error = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+
+        let mut targets: Vec<(&str, &str)> = channel_ids
+            .iter()
+            .map(|c| (c.as_str(), ""))
+            .chain(guild_ids.iter().map(|g| ("", g.as_str())))
+            .collect();
+        if targets.is_empty() {
+            targets.push(("", ""));
+        }
+
+        let mut tx = pool.begin().await.map_err(internal_error)?;
+
+        let delete = "
+            DELETE FROM listener
+            WHERE flow_id = $1 AND flows_user = $2
         ";
-        _ = sqlx::query(insert)
-            .bind(flow_id)
-            .bind(flows_user)
-            .bind(channel_id)
-            .bind(bot_token)
-            .execute(pool)
+        _ = sqlx::query(delete)
+            .bind(&flow.flow_id)
+            .bind(&flow.flows_user)
+            .execute(&mut tx)
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .map_err(internal_error)?;
+
+        let insert = "
+            INSERT INTO listener(
+                flow_id, flows_user, channel_id, guild_id, bot_token, bot_fingerprint, event_filter,
+                auto_defer
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (flow_id, flows_user, channel_id, guild_id) DO NOTHING
+        ";
+        let encrypted = encrypt(bot_token);
+        let bot_fingerprint = fingerprint(bot_token);
+        for (channel_id, guild_id) in targets {
+            _ = sqlx::query(insert)
+                .bind(&flow.flow_id)
+                .bind(&flow.flows_user)
+                .bind(channel_id)
+                .bind(guild_id)
+                .bind(&encrypted)
+                .bind(&bot_fingerprint)
+                .bind(event_filter)
+                .bind(auto_defer)
+                .execute(&mut tx)
+                .await
+                .map_err(internal_error)?;
+        }
+
+        tx.commit().await.map_err(internal_error)?;
 
         Ok(())
     }
 
-    pub async fn select_old(
-        flow_id: &str,
-        flows_user: &str,
-        channel_id: &str,
-        pool: &PgPool,
-    ) -> Option<Bot> {
-        // select old token
+    pub async fn select_old(flow_id: &str, flows_user: &str, pool: &PgPool) -> Vec<BotFingerprint> {
+        // select old bots
         let select = "
-        SELECT bot_token
-        FROM listener
-        WHERE flow_id = $1 AND flows_user = $2 AND channel_id = $3
-    ";
+            SELECT DISTINCT bot_fingerprint
+            FROM listener
+            WHERE flow_id = $1 AND flows_user = $2
+        ";
         sqlx::query_as(select)
             .bind(flow_id)
             .bind(flows_user)
-            .bind(channel_id)
-            .fetch_optional(pool)
+            .fetch_all(pool)
             .await
-            .ok()?
+            .unwrap_or_default()
     }
 }
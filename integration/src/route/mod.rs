@@ -4,10 +4,12 @@ mod connected;
 mod listen;
 mod proxy;
 mod static_path;
+mod status;
 
 pub use access::access;
 pub use auth::auth;
 pub use connected::connected;
-pub use listen::listen;
+pub use listen::{listen, listen_to_channel, unlisten};
 pub use proxy::proxy;
 pub use static_path::static_path;
+pub use status::status;
@@ -1,12 +1,22 @@
 use axum::{
     body::Body,
-    extract::Path,
-    http::{response::Builder, HeaderValue, Request, Response, Uri},
+    extract::{Path, State},
+    http::{response::Builder, HeaderValue, Method, Request, Response, StatusCode, Uri},
 };
+use percent_encoding::percent_decode_str;
 use reqwest::Request as RRequest;
 use serde::Deserialize;
+use sqlx::PgPool;
 
-use crate::{shared::get_client, DEFAULT_BOT_PLACEHOLDER, DEFAULT_TOKEN};
+use crate::{
+    shared::get_client,
+    state::AppState,
+    utils::{
+        jwt::{LegacyClaim, FLOWS_JWT_HEADER},
+        permission::{authorized_channel, authorized_guild},
+    },
+    DEFAULT_BOT_PLACEHOLDER, DEFAULT_TOKEN,
+};
 
 #[derive(Deserialize)]
 pub struct PF {
@@ -21,12 +31,26 @@ pub enum DiscordApi {
     Status,
 }
 
-pub async fn proxy(Path(PF { api, path }): Path<PF>, mut req: Request<Body>) -> Response<Body> {
+pub async fn proxy(
+    Path(PF { api, path }): Path<PF>,
+    State(state): State<AppState>,
+    claim: LegacyClaim,
+    mut req: Request<Body>,
+) -> Result<Response<Body>, (StatusCode, String)> {
     let hds = req.headers_mut();
-    let x_token = hds.remove("Authorization").unwrap();
-    let token = x_token.to_str().unwrap().strip_prefix("Bot ").unwrap();
+    hds.remove(FLOWS_JWT_HEADER);
+    let x_token = hds.remove("Authorization");
+    let token = x_token
+        .as_ref()
+        .and_then(|t| t.to_str().ok())
+        .and_then(|t| t.strip_prefix("Bot "))
+        .ok_or((StatusCode::UNAUTHORIZED, String::from("Missing bot token")))?;
 
     let token = if token == DEFAULT_BOT_PLACEHOLDER {
+        let claim = claim.required()?;
+        if let DiscordApi::Api = api {
+            authorized_path(&claim.flows_user, &path, req.method(), &state.pool).await?;
+        }
         &*DEFAULT_TOKEN
     } else {
         token
@@ -72,8 +96,124 @@ pub async fn proxy(Path(PF { api, path }): Path<PF>, mut req: Request<Body>) ->
             headers.remove("Transfer-Encoding");
             let body = Body::from(text);
 
-            builder.body(body).unwrap()
+            Ok(builder.body(body).unwrap())
+        }
+        Err(_) => Ok(Response::new(Body::empty())),
+    }
+}
+
+/// The default bot is shared by all the flows users, so its calls are only
+/// proxied when they touch a guild or a channel the user has authorized.
+async fn authorized_path(
+    flows_user: &str,
+    path: &str,
+    method: &Method,
+    pool: &PgPool,
+) -> Result<(), (StatusCode, String)> {
+    let segments = path_segments(path)?;
+
+    let authorized = match segments.as_slice() {
+        // Holding the token of the webhook or the interaction is enough
+        ["webhooks", _, _, ..] | ["interactions", ..] => true,
+        ["users", "@me"] | ["oauth2", "applications", "@me"] => method == Method::GET,
+        ["channels", channel_id, ..] => authorized_channel(flows_user, channel_id, pool).await?,
+        ["guilds", guild_id, ..] | ["applications", _, "guilds", guild_id, ..] => {
+            authorized_guild(flows_user, guild_id, pool).await
+        }
+        _ => false,
+    };
+
+    match authorized {
+        true => Ok(()),
+        false => Err((
+            StatusCode::FORBIDDEN,
+            format!("Not authorized path: {}", path),
+        )),
+    }
+}
+
+/// The segments of the proxied path, which must be the ones of the URL sent
+/// to Discord.
+///
+/// The URL parser resolves the dot segments, percent-encoded or not, and
+/// reads the backslashes as slashes, so a path with any of them, or with
+/// empty segments, is refused rather than authorized on segments which are
+/// not the ones requested. The segments are decoded until they no longer
+/// change, as any layer between the flow and Discord may decode them once
+/// more.
+fn path_segments(path: &str) -> Result<Vec<&str>, (StatusCode, String)> {
+    let segments: Vec<&str> = path.split('/').collect();
+
+    for segment in segments.iter() {
+        let mut decoded = segment.to_string();
+        loop {
+            let next = percent_decode_str(&decoded)
+                .decode_utf8_lossy()
+                .into_owned();
+            if next == decoded {
+                break;
+            }
+            decoded = next;
+        }
+
+        if decoded.is_empty() || decoded == "." || decoded == ".." || decoded.contains(['/', '\\'])
+        {
+            return Err((StatusCode::BAD_REQUEST, format!("Invalid path: {}", path)));
+        }
+    }
+
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_the_path() {
+        let segments = path_segments("channels/123/messages").unwrap();
+
+        assert_eq!(segments, ["channels", "123", "messages"]);
+    }
+
+    #[test]
+    fn refuses_the_dot_segments() {
+        for path in [
+            "interactions/../guilds/1/members",
+            "webhooks/a/b/../../guilds/1/bans/2",
+            "channels/123/../../guilds/456/roles",
+            "channels/123/./messages",
+        ] {
+            let (status, _) = path_segments(path).unwrap_err();
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", path);
+        }
+    }
+
+    #[test]
+    fn refuses_the_encoded_dot_segments() {
+        for path in [
+            "interactions/%2e%2e/guilds/1/members",
+            "webhooks/a/b/%2E./.%2e/guilds/1/bans/2",
+            "channels/123/%252e%252e/%252e%252e/guilds/456/roles",
+            "channels/123/%2e/messages",
+        ] {
+            let (status, _) = path_segments(path).unwrap_err();
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", path);
+        }
+    }
+
+    #[test]
+    fn refuses_the_empty_segments_and_separators() {
+        for path in [
+            "channels//guilds/456",
+            "/guilds/456",
+            "channels/123/",
+            "channels/123\\..\\..\\guilds/456",
+            "channels/123%5c..%5c../guilds/456",
+            "channels/123%2f..%2f../guilds/456",
+        ] {
+            let (status, _) = path_segments(path).unwrap_err();
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", path);
         }
-        Err(_) => Response::new(Body::empty()),
     }
 }
@@ -0,0 +1,30 @@
+use axum::Json;
+use serde_json::{json, Value};
+
+use crate::shared::{shard_map, startup_progress, RunningBot};
+
+/// The progress of starting the bots on boot, and the bots connected to the
+/// gateway with the status of their shards.
+///
+/// The endpoint isn't authenticated: the bots are neither identified nor
+/// given their errors, only whether they were restarted after one.
+pub async fn status() -> Json<Value> {
+    let startup = json!(*startup_progress().lock().await);
+
+    // The shard managers are locked by the clients as they run, the map is
+    // released before waiting for them
+    let running: Vec<RunningBot> = shard_map().lock().await.values().cloned().collect();
+
+    let mut bots = Vec::with_capacity(running.len());
+    for bot in running {
+        bots.push(json!({
+            "intents": bot.intents.bits(),
+            "shards": bot.shards,
+            "max_concurrency": bot.max_concurrency,
+            "restarted": bot.last_error.is_some(),
+            "shard_status": bot.shard_status().await,
+        }));
+    }
+
+    Json(json!({ "startup": startup, "bots": bots }))
+}
@@ -1,18 +1,107 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use once_cell::sync::OnceCell;
 use reqwest::Client;
-use serenity::client::bridge::gateway::ShardManager;
+use serde::Serialize;
+use serenity::{client::bridge::gateway::ShardManager, model::gateway::GatewayIntents};
 use tokio::sync::Mutex;
 
-type ShardMap = Mutex<HashMap<String, Arc<Mutex<ShardManager>>>>;
+/// A bot connected to the gateway.
+#[derive(Clone)]
+pub struct RunningBot {
+    /// The shard managers of the clients of the bot, one per shard so that
+    /// the shards can identify concurrently.
+    pub shard_managers: Vec<Arc<Mutex<ShardManager>>>,
+    pub intents: GatewayIntents,
+    /// The number of shards the bot is started with.
+    pub shards: u64,
+    /// How many shards identify at once.
+    pub max_concurrency: u64,
+    /// The error a client last stopped on before being restarted.
+    pub last_error: Option<String>,
+}
+
+/// The status of a shard of a running bot.
+#[derive(Serialize)]
+pub struct ShardStatus {
+    pub id: u64,
+    /// The connection stage, `Connected` once it receives events.
+    pub stage: String,
+    /// The latency of the last heartbeat.
+    pub latency_ms: Option<u128>,
+}
+
+impl RunningBot {
+    /// Whether the shard manager is the one of a client of the bot.
+    pub fn runs(&self, shard_manager: &Arc<Mutex<ShardManager>>) -> bool {
+        self.shard_managers
+            .iter()
+            .any(|m| Arc::ptr_eq(m, shard_manager))
+    }
+
+    /// Shut down the shards of all the clients of the bot.
+    pub async fn shutdown(&self) {
+        for shard_manager in self.shard_managers.iter() {
+            shard_manager.lock().await.shutdown_all().await;
+        }
+    }
+
+    /// The status of the shards started so far, the others are still
+    /// queued.
+    pub async fn shard_status(&self) -> Vec<ShardStatus> {
+        let mut shards = Vec::with_capacity(self.shard_managers.len());
+        for shard_manager in self.shard_managers.iter() {
+            let runners = shard_manager.lock().await.runners.clone();
+            let runners = runners.lock().await;
+
+            shards.extend(runners.iter().map(|(id, runner)| ShardStatus {
+                id: id.0,
+                stage: runner.stage.to_string(),
+                latency_ms: runner.latency.map(|l| l.as_millis()),
+            }));
+        }
+        shards.sort_by_key(|s| s.id);
+
+        shards
+    }
+}
+
+/// The progress of starting the bots on boot.
+#[derive(Default, Serialize)]
+pub struct StartupProgress {
+    /// The number of bots to start, the default one included.
+    pub total: usize,
+    pub started: usize,
+    pub failed: usize,
+    /// The bots not started as their token is known to be invalid.
+    pub skipped: usize,
+    pub done: bool,
+}
+
+pub fn startup_progress() -> &'static Mutex<StartupProgress> {
+    static INSTANCE: OnceCell<Mutex<StartupProgress>> = OnceCell::new();
+    INSTANCE.get_or_init(|| Mutex::new(StartupProgress::default()))
+}
+
+type ShardMap = Mutex<HashMap<String, RunningBot>>;
 
 pub fn shard_map() -> &'static ShardMap {
     static INSTANCE: OnceCell<ShardMap> = OnceCell::new();
     INSTANCE.get_or_init(|| Mutex::new(HashMap::new()))
 }
 
+/// How long the shared client waits for a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the shared client waits for a whole request, response included.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub fn get_client() -> &'static Client {
     static INS: OnceCell<Client> = OnceCell::new();
-    INS.get_or_init(Client::new)
+    INS.get_or_init(|| {
+        Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap()
+    })
 }
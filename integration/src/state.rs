@@ -1,75 +1,256 @@
-use std::{future::Future, sync::Arc};
+use std::{
+    collections::{hash_map::RandomState, HashSet},
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    time::Duration,
+};
 
 use crate::{
-    handler::Handler, model::Bot, shared::shard_map, GatewayIntents, DEFAULT_BOT_PLACEHOLDER,
-    DEFAULT_TOKEN,
+    intents,
+    model::{Bot, BotFingerprint},
+    shared::{shard_map, startup_progress, RunningBot},
+    supervisor::{build_clients, supervise, Supervised, IDENTIFY_INTERVAL},
+    utils::{
+        crypto::{decrypt, fingerprint},
+        database::clear_fatal_bot_error,
+        http::gateway_bot,
+    },
+    DEFAULT_BOT_PLACEHOLDER, DEFAULT_TOKEN,
 };
-use serenity::Client;
 use sqlx::PgPool;
+use tokio::sync::Semaphore;
+
+/// How many bots identify at once on boot.
+const STARTUP_CONCURRENCY: usize = 4;
+/// The longest random delay before starting a bot, so that the bots of a
+/// deploy don't identify in lockstep.
+const STARTUP_JITTER: Duration = Duration::from_secs(1);
 
 #[derive(Clone)]
 pub struct AppState {
     pub pool: Arc<PgPool>,
 }
 
+/// The token to connect with, the one of the default bot for its placeholder.
+pub fn real_token(token: &str) -> &str {
+    match token == DEFAULT_BOT_PLACEHOLDER {
+        true => DEFAULT_TOKEN.as_str(),
+        false => token,
+    }
+}
+
 impl AppState {
+    /// Connect the bot to the gateway with the intents its listeners need,
+    /// reconnecting it when it is already connected with fewer of them.
+    ///
+    /// The bot is started with the number of shards Discord recommends, the
+    /// default bot being in too many guilds for a single one, with a client
+    /// per shard so that they identify `max_concurrency` at a time (see
+    /// [`supervise`]).
+    ///
+    /// The clients are then supervised, restarted whenever they stop on a
+    /// transient error. `cb` is called with whether the bot was shut down
+    /// rather than failing for good, unless it was already connected with
+    /// all the intents.
     pub async fn start_client<F, Fut>(&self, token: String, cb: F) -> serenity::Result<()>
     where
         F: FnOnce(bool) -> Fut + std::marker::Send + 'static,
-        Fut: Future<Output = ()> + std::marker::Send,
+        Fut: Future<Output = ()> + std::marker::Send + 'static,
     {
-        let mut guard = shard_map().lock().await;
-        let shard = guard.get(&token);
-        if shard.is_some() {
-            return Ok(());
-        }
+        let fingerprint = fingerprint(&token);
+        let real_token = real_token(&token);
 
-        let intents = GatewayIntents::all();
-        let real_token = match token == DEFAULT_BOT_PLACEHOLDER {
-            true => DEFAULT_TOKEN.as_str(),
-            false => token.as_str(),
-        };
-        let mut client = Client::builder(real_token, intents)
-            .event_handler(Handler {
-                token: token.clone(),
-                pool: self.pool.clone(),
-            })
-            .await
-            .unwrap();
+        // The shard map is only locked to read and swap the entry of the bot,
+        // not while the clients are built or the previous ones shut down, the
+        // entry being checked unchanged before it is replaced
+        let (clients, intents, gateway, replaced) = loop {
+            let running = shard_map().lock().await.get(&fingerprint).cloned();
+            let needed = intents::needed(&fingerprint, &self.pool).await;
+            if running.as_ref().is_some_and(|r| r.intents.contains(needed)) {
+                return Ok(());
+            }
 
-        let shard_manager = client.shard_manager.clone();
+            // Only the bots to (re)connect read their application and gateway
+            let mut intents = intents::required(real_token, &fingerprint, needed).await;
+            if let Some(running) = &running {
+                if running.intents.contains(intents) {
+                    return Ok(());
+                }
 
-        guard.insert(token, shard_manager);
-        drop(guard);
+                // Never narrow the intents of a running bot, a listener
+                // registered concurrently may need them
+                intents |= running.intents;
+            }
+            let gateway = gateway_bot(real_token).await;
+            let (shards, max_concurrency) = gateway.as_ref().map_or((1, 1), |g| {
+                (g.shards.max(1), g.session_start_limit.max_concurrency)
+            });
+            let clients =
+                build_clients(real_token, intents, &fingerprint, shards, &self.pool).await?;
+            let shard_managers: Vec<_> = clients.iter().map(|c| c.shard_manager.clone()).collect();
 
-        tokio::spawn(async move {
-            cb(client.start().await.is_ok()).await;
-        });
+            let mut guard = shard_map().lock().await;
+            let unchanged = match (guard.get(&fingerprint), &running) {
+                (None, None) => true,
+                (Some(current), Some(running)) => {
+                    running.shard_managers.iter().any(|m| current.runs(m))
+                }
+                _ => false,
+            };
+            if !unchanged {
+                drop(guard);
+                for shard_manager in shard_managers {
+                    shard_manager.lock().await.shutdown_all().await;
+                }
+                continue;
+            }
+
+            let replaced = guard.insert(
+                fingerprint.clone(),
+                RunningBot {
+                    shard_managers,
+                    intents,
+                    shards,
+                    max_concurrency,
+                    last_error: None,
+                },
+            );
+            break (clients, intents, gateway, replaced);
+        };
+
+        if let Some(replaced) = replaced {
+            log::info!(
+                "Reconnecting bot {} with intents {:?}",
+                fingerprint,
+                intents
+            );
+            replaced.shutdown().await;
+        }
+
+        clear_fatal_bot_error(&fingerprint, &self.pool).await;
+        let supervised = Supervised {
+            token: real_token.to_string(),
+            fingerprint,
+            intents,
+            pool: self.pool.clone(),
+            gateway,
+        };
+        tokio::spawn(supervise(supervised, clients, cb));
 
         Ok(())
     }
 
+    /// Start the bots on boot, the default one first, then the others
+    /// [`STARTUP_CONCURRENCY`] at a time so that a deploy doesn't trip the
+    /// identify limits.
+    ///
+    /// The progress is logged and reported by the status endpoint.
     pub async fn listen_ws(&self) {
-        self.start_default_client().await;
-        self.start_user_client().await;
-    }
+        let tokens = self.user_tokens().await;
+        log::info!("Starting the default bot and {} others", tokens.len());
+        startup_progress().lock().await.total += tokens.len() + 1;
+
+        self.start_paced(DEFAULT_BOT_PLACEHOLDER.to_string()).await;
 
-    async fn start_default_client(&self) {
-        _ = self
-            .start_client(DEFAULT_BOT_PLACEHOLDER.to_string(), |_| async {})
-            .await;
+        let slots = Arc::new(Semaphore::new(STARTUP_CONCURRENCY));
+        let mut tasks = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            let slot = slots.clone().acquire_owned().await.unwrap();
+            let state = self.clone();
+            tasks.push(tokio::spawn(async move {
+                state.start_paced(token).await;
+                drop(slot);
+            }));
+        }
+        for task in tasks {
+            _ = task.await;
+        }
+
+        let mut progress = startup_progress().lock().await;
+        progress.done = true;
+        log::info!(
+            "Started {} bots, {} failed and {} skipped",
+            progress.started,
+            progress.failed,
+            progress.skipped
+        );
     }
 
-    async fn start_user_client(&self) {
-        let sql = "SELECT DISTINCT bot_token FROM listener";
-        let bots: Vec<Bot> = sqlx::query_as(sql)
+    /// The tokens of the custom bots to start, skipping the ones whose client
+    /// stopped on a fatal error.
+    async fn user_tokens(&self) -> Vec<String> {
+        let select = "
+            SELECT DISTINCT ON (bot_fingerprint) bot_token
+            FROM listener
+            WHERE bot_fingerprint IS DISTINCT FROM $1
+        ";
+        let bots: Vec<Bot> = sqlx::query_as(select)
+            .bind(DEFAULT_BOT_PLACEHOLDER)
             .fetch_all(&*self.pool)
             .await
             .map_err(|e| e.to_string())
             .unwrap();
 
-        for Bot { token } in bots {
-            _ = self.start_client(token, |_| async {}).await;
+        let select = "SELECT bot_fingerprint FROM bot_status WHERE fatal";
+        let invalid: HashSet<String> = sqlx::query_as(select)
+            .fetch_all(&*self.pool)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|BotFingerprint { fingerprint }| fingerprint)
+            .collect();
+
+        let mut tokens = Vec::with_capacity(bots.len());
+        let mut skipped = 0;
+        for token in bots.iter().filter_map(|bot| decrypt(&bot.token)) {
+            let fingerprint = fingerprint(&token);
+            if invalid.contains(&fingerprint) {
+                log::info!("Skipping bot {}, its token is invalid", fingerprint);
+                skipped += 1;
+                continue;
+            }
+            tokens.push(token);
+        }
+        startup_progress().lock().await.skipped += skipped;
+
+        tokens
+    }
+
+    /// Start the bot after a random delay, then hold its startup slot for
+    /// [`IDENTIFY_INTERVAL`].
+    ///
+    /// The client identifies in the background, the slot is held for the
+    /// time an identify takes, not until the bot is ready.
+    async fn start_paced(&self, token: String) {
+        tokio::time::sleep(jitter()).await;
+
+        let fingerprint = fingerprint(&token);
+        let result = self.start_client(token, |_| async {}).await;
+
+        let mut progress = startup_progress().lock().await;
+        match &result {
+            Ok(()) => progress.started += 1,
+            Err(e) => {
+                log::warn!("Failed to start bot {}: {}", fingerprint, e);
+                progress.failed += 1;
+            }
+        }
+        log::info!(
+            "Started {}/{} bots",
+            progress.started + progress.failed,
+            progress.total
+        );
+        drop(progress);
+
+        if result.is_ok() {
+            tokio::time::sleep(IDENTIFY_INTERVAL).await;
         }
     }
 }
+
+/// A random delay of up to [`STARTUP_JITTER`].
+fn jitter() -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    Duration::from_millis(random % STARTUP_JITTER.as_millis() as u64)
+}
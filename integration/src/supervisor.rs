@@ -0,0 +1,289 @@
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serenity::{
+    client::bridge::gateway::ShardManager, gateway::GatewayError, model::gateway::GatewayIntents,
+    Client,
+};
+use sqlx::PgPool;
+use tokio::sync::Mutex;
+
+use crate::{
+    handler::Handler,
+    model::GatewayBot,
+    shared::{shard_map, RunningBot},
+    utils::{database::record_bot_error, http::gateway_bot},
+};
+
+/// The delay before the first restart of a client, doubled on every failure.
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+/// The time Discord needs between two identifies of the same rate limit key.
+pub const IDENTIFY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The clients of a bot started by [`AppState::start_client`], restarted
+/// until they are shut down or fail for good.
+///
+/// [`AppState::start_client`]: crate::state::AppState::start_client
+pub struct Supervised {
+    pub token: String,
+    pub fingerprint: String,
+    pub intents: GatewayIntents,
+    pub pool: Arc<PgPool>,
+    /// The gateway information read to start the clients, if any.
+    pub gateway: Option<GatewayBot>,
+}
+
+/// The callback of [`supervise`], called by the first client of the bot
+/// which stops for good.
+type Callback<F> = Arc<std::sync::Mutex<Option<F>>>;
+
+pub async fn build_client(
+    token: &str,
+    intents: GatewayIntents,
+    fingerprint: &str,
+    pool: &Arc<PgPool>,
+) -> serenity::Result<Client> {
+    Client::builder(token, intents)
+        .event_handler(Handler::new(fingerprint.to_string(), pool.clone()))
+        .await
+}
+
+/// Build a client per shard of the bot.
+pub async fn build_clients(
+    token: &str,
+    intents: GatewayIntents,
+    fingerprint: &str,
+    shards: u64,
+    pool: &Arc<PgPool>,
+) -> serenity::Result<Vec<Client>> {
+    let mut clients = Vec::with_capacity(shards as usize);
+    for _ in 0..shards {
+        clients.push(build_client(token, intents, fingerprint, pool).await?);
+    }
+
+    Ok(clients)
+}
+
+/// Start the clients of the bot, one per shard, then supervise them.
+///
+/// The shards whose ids modulo `max_concurrency` differ can identify at the
+/// same time, so they are started `max_concurrency` at a time, every
+/// [`IDENTIFY_INTERVAL`].
+///
+/// A client is only stopped for good when it is shut down, its shard
+/// manager being removed from the shard map or replaced, or on a fatal
+/// error: an invalid token or intents the application is not allowed, which
+/// shuts down the whole bot. `cb` is then called with whether it was shut
+/// down rather than failing.
+pub async fn supervise<F, Fut>(supervised: Supervised, clients: Vec<Client>, cb: F)
+where
+    F: FnOnce(bool) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let shards = clients.len() as u64;
+    let max_concurrency = supervised
+        .gateway
+        .as_ref()
+        .map_or(1, |g| g.session_start_limit.max_concurrency.max(1));
+    if let Some(gateway) = &supervised.gateway {
+        wait_for_session_starts(&supervised.fingerprint, shards, gateway).await;
+    }
+
+    let supervised = Arc::new(supervised);
+    let cb: Callback<F> = Arc::new(std::sync::Mutex::new(Some(cb)));
+    for (shard, client) in (0..shards).zip(clients) {
+        if shard > 0 && shard % max_concurrency == 0 {
+            tokio::time::sleep(IDENTIFY_INTERVAL).await;
+        }
+        tokio::spawn(supervise_shard(
+            supervised.clone(),
+            (shard, shards),
+            client,
+            cb.clone(),
+        ));
+    }
+}
+
+/// Run the client of a shard, restarting it with an exponential backoff
+/// whenever it stops on a transient error.
+async fn supervise_shard<F, Fut>(
+    supervised: Arc<Supervised>,
+    (shard, shards): (u64, u64),
+    mut client: Client,
+    cb: Callback<F>,
+) where
+    F: FnOnce(bool) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let Supervised {
+        token,
+        fingerprint,
+        intents,
+        pool,
+        ..
+    } = &*supervised;
+    let mut backoff = BASE_BACKOFF;
+
+    loop {
+        let started = Instant::now();
+        let result = client.start_shard(shard, shards).await;
+        let shard_manager = client.shard_manager.clone();
+
+        if !is_current(fingerprint, &shard_manager).await {
+            call_once(&cb, true).await;
+            return;
+        }
+
+        let error = match result {
+            Ok(()) => String::from("The client stopped"),
+            Err(e) if is_fatal(&e) => {
+                log::error!("Bot {} stopped for good: {}", fingerprint, e);
+                record_bot_error(fingerprint, &e.to_string(), true, pool).await;
+                let removed = remove(fingerprint, &shard_manager).await;
+                call_once(&cb, false).await;
+                if let Some(bot) = removed {
+                    bot.shutdown().await;
+                }
+                return;
+            }
+            Err(e) => e.to_string(),
+        };
+
+        // A client running for a while failed for a new reason
+        if started.elapsed() > MAX_BACKOFF {
+            backoff = BASE_BACKOFF;
+        }
+        log::warn!(
+            "Shard {} of bot {} stopped, restarting it in {}s: {}",
+            shard,
+            fingerprint,
+            backoff.as_secs(),
+            error
+        );
+        record_bot_error(fingerprint, &error, false, pool).await;
+        set_last_error(fingerprint, &shard_manager, &error).await;
+
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+
+        // Build a new client, unless the bot was shut down in the meantime
+        loop {
+            match build_client(token, *intents, fingerprint, pool).await {
+                Ok(new) => {
+                    if !replace(fingerprint, &shard_manager, &new.shard_manager).await {
+                        call_once(&cb, true).await;
+                        return;
+                    }
+                    client = new;
+                    break;
+                }
+                Err(e) => {
+                    log::warn!("Failed to build the client of bot {}: {}", fingerprint, e);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+        if let Some(gateway) = gateway_bot(token).await {
+            wait_for_session_starts(fingerprint, 1, &gateway).await;
+        }
+    }
+}
+
+/// Call the callback, unless another client of the bot already did.
+async fn call_once<F, Fut>(cb: &Callback<F>, shut_down: bool)
+where
+    F: FnOnce(bool) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let cb = cb.lock().unwrap().take();
+    if let Some(cb) = cb {
+        cb(shut_down).await;
+    }
+}
+
+/// The errors a restart won't fix.
+fn is_fatal(e: &serenity::Error) -> bool {
+    match e {
+        serenity::Error::Gateway(e) => matches!(
+            e,
+            GatewayError::InvalidAuthentication
+                | GatewayError::InvalidGatewayIntents
+                | GatewayError::DisallowedGatewayIntents
+        ),
+        serenity::Error::Http(e) => e.status_code().map(|s| s.as_u16()) == Some(401),
+        _ => false,
+    }
+}
+
+/// Wait for the session start limit of the bot to reset when it can't start
+/// `shards` shards, Discord resets the token of the bots exceeding it.
+async fn wait_for_session_starts(fingerprint: &str, shards: u64, gateway: &GatewayBot) {
+    let limit = &gateway.session_start_limit;
+    if limit.remaining >= shards {
+        return;
+    }
+
+    log::warn!(
+        "Bot {} can only start {} of its {} shards, waiting {}s for the limit to reset",
+        fingerprint,
+        limit.remaining,
+        shards,
+        limit.reset_after / 1000
+    );
+    tokio::time::sleep(Duration::from_millis(limit.reset_after)).await;
+}
+
+/// Whether the shard manager is still one of the bot in the shard map.
+async fn is_current(fingerprint: &str, shard_manager: &Arc<Mutex<ShardManager>>) -> bool {
+    let guard = shard_map().lock().await;
+    guard
+        .get(fingerprint)
+        .is_some_and(|bot| bot.runs(shard_manager))
+}
+
+async fn set_last_error(fingerprint: &str, shard_manager: &Arc<Mutex<ShardManager>>, error: &str) {
+    let mut guard = shard_map().lock().await;
+    if let Some(bot) = guard.get_mut(fingerprint) {
+        if bot.runs(shard_manager) {
+            bot.last_error = Some(error.to_string());
+        }
+    }
+}
+
+/// Replace the shard manager of a client of the bot by the one of its new
+/// client, unless the bot was shut down or restarted by someone else.
+async fn replace(
+    fingerprint: &str,
+    old: &Arc<Mutex<ShardManager>>,
+    new: &Arc<Mutex<ShardManager>>,
+) -> bool {
+    let mut guard = shard_map().lock().await;
+    let current = guard
+        .get_mut(fingerprint)
+        .and_then(|bot| bot.shard_managers.iter_mut().find(|m| Arc::ptr_eq(m, old)));
+    match current {
+        Some(current) => {
+            *current = new.clone();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Remove the bot from the shard map, if the shard manager is still one of
+/// its clients, returning it to be shut down.
+async fn remove(fingerprint: &str, shard_manager: &Arc<Mutex<ShardManager>>) -> Option<RunningBot> {
+    let mut guard = shard_map().lock().await;
+    match guard
+        .get(fingerprint)
+        .is_some_and(|bot| bot.runs(shard_manager))
+    {
+        true => guard.remove(fingerprint),
+        false => None,
+    }
+}
@@ -1,5 +1,7 @@
 pub mod http {
-    use crate::shared::get_client;
+    use serde::Deserialize;
+
+    use crate::{model::GatewayBot, shared::get_client};
 
     pub async fn check_token(token: &str) -> bool {
         let url = "https://discord.com/api/users/@me";
@@ -20,30 +22,362 @@ pub mod http {
 
         false
     }
+
+    /// The `flags` of the application of the bot, telling which privileged
+    /// intents it is allowed.
+    pub async fn application_flags(token: &str) -> Option<u64> {
+        #[derive(Deserialize)]
+        struct Application {
+            #[serde(default)]
+            flags: u64,
+        }
+
+        let url = "https://discord.com/api/applications/@me";
+
+        let client = get_client();
+        let resp = client
+            .get(url)
+            .header("Authorization", format!("Bot {token}"))
+            .send()
+            .await
+            .ok()?;
+        if !resp.status().is_success() {
+            return None;
+        }
+
+        resp.json::<Application>().await.ok().map(|a| a.flags)
+    }
+
+    /// The recommended number of shards of the bot and how many sessions it
+    /// may still start.
+    pub async fn gateway_bot(token: &str) -> Option<GatewayBot> {
+        let url = "https://discord.com/api/gateway/bot";
+
+        let client = get_client();
+        let resp = client
+            .get(url)
+            .header("Authorization", format!("Bot {token}"))
+            .send()
+            .await
+            .ok()?;
+        if !resp.status().is_success() {
+            return None;
+        }
+
+        resp.json().await.ok()
+    }
+}
+
+pub mod crypto {
+    use aes_gcm::{
+        aead::{Aead, AeadCore, KeyInit, OsRng},
+        Aes256Gcm, Key, Nonce,
+    };
+    use once_cell::sync::OnceCell;
+    use sha2::{Digest, Sha256};
+
+    use crate::{DEFAULT_BOT_PLACEHOLDER, TOKEN_ENCRYPTION_KEY};
+
+    const NONCE_LEN: usize = 12;
+
+    fn cipher() -> &'static Aes256Gcm {
+        static INS: OnceCell<Aes256Gcm> = OnceCell::new();
+        INS.get_or_init(|| {
+            let key = hex::decode(TOKEN_ENCRYPTION_KEY.as_str())
+                .expect("env `TOKEN_ENCRYPTION_KEY` is not hex encoded");
+            assert!(
+                key.len() == 32,
+                "env `TOKEN_ENCRYPTION_KEY` must be 32 bytes"
+            );
+            Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+        })
+    }
+
+    /// Identify a bot without holding its token, used as the key of the
+    /// listeners and of the shard map.
+    pub fn fingerprint(bot_token: &str) -> String {
+        if bot_token == DEFAULT_BOT_PLACEHOLDER {
+            return bot_token.to_string();
+        }
+
+        hex::encode(Sha256::digest(bot_token.as_bytes()))
+    }
+
+    /// Encrypt the token to be stored, as the hex of the nonce followed by
+    /// the ciphertext.
+    pub fn encrypt(bot_token: &str) -> String {
+        if bot_token == DEFAULT_BOT_PLACEHOLDER {
+            return bot_token.to_string();
+        }
+
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher()
+            .encrypt(&nonce, bot_token.as_bytes())
+            .expect("token encryption failed");
+
+        hex::encode([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    pub fn decrypt(encrypted: &str) -> Option<String> {
+        if encrypted == DEFAULT_BOT_PLACEHOLDER {
+            return Some(encrypted.to_string());
+        }
+
+        let bytes = hex::decode(encrypted).ok()?;
+        if bytes.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let plaintext = cipher()
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .ok()?;
+
+        String::from_utf8(plaintext).ok()
+    }
+}
+
+pub mod jwt {
+    use axum::{
+        async_trait,
+        extract::FromRequestParts,
+        http::{request::Parts, StatusCode},
+    };
+    use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+    use serde::de::DeserializeOwned;
+
+    use crate::model::FlowsClaim;
+
+    /// The header carrying the JWT the platform signs for a flows user.
+    pub const FLOWS_JWT_HEADER: &str = "X-Flows-Jwt";
+
+    /// Decode a RS256 JWT signed with the key of the flows platform.
+    pub fn decode_claim<T: DeserializeOwned>(token: &str) -> Result<T, String> {
+        let mut val = Validation::new(Algorithm::RS256);
+        // Set the time skew
+        val.leeway = 60;
+        let public_key = std::env::var("FLOWS_JWT_PUBLIC_KEY").unwrap();
+
+        decode::<T>(
+            token,
+            &DecodingKey::from_rsa_pem(public_key.as_bytes()).map_err(|e| e.to_string())?,
+            &val,
+        )
+        .map(|data| data.claims)
+        .map_err(|e| e.to_string())
+    }
+
+    #[async_trait]
+    impl<S: Send + Sync> FromRequestParts<S> for FlowsClaim {
+        type Rejection = (StatusCode, String);
+
+        async fn from_request_parts(
+            parts: &mut Parts,
+            _state: &S,
+        ) -> Result<Self, Self::Rejection> {
+            let token = parts
+                .headers
+                .get(FLOWS_JWT_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .ok_or((
+                    StatusCode::UNAUTHORIZED,
+                    String::from("Missing flows token"),
+                ))?;
+
+            decode_claim(token).map_err(|e| (StatusCode::UNAUTHORIZED, e))
+        }
+    }
+
+    /// The claim of the legacy routes, still called by the SDK versions
+    /// which don't send the flows JWT.
+    ///
+    /// A missing JWT is let through, with a warning, until
+    /// [`FLOWS_JWT_ENFORCED`] is set, except for the calls on behalf of the
+    /// default bot (see [`LegacyClaim::required`]). A JWT which is sent is
+    /// always verified.
+    ///
+    /// [`FLOWS_JWT_ENFORCED`]: crate::FLOWS_JWT_ENFORCED
+    pub struct LegacyClaim(pub Option<FlowsClaim>);
+
+    #[async_trait]
+    impl<S: Send + Sync> FromRequestParts<S> for LegacyClaim {
+        type Rejection = (StatusCode, String);
+
+        async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+            if parts.headers.contains_key(FLOWS_JWT_HEADER) || *crate::FLOWS_JWT_ENFORCED {
+                return FlowsClaim::from_request_parts(parts, state)
+                    .await
+                    .map(|claim| LegacyClaim(Some(claim)));
+            }
+
+            log::warn!(
+                "Accepted a call to {} without flows token",
+                parts.uri.path()
+            );
+            Ok(LegacyClaim(None))
+        }
+    }
+
+    impl LegacyClaim {
+        /// The claim, whether or not [`FLOWS_JWT_ENFORCED`] is set: the
+        /// default bot is shared by all the flows users, the calls on its
+        /// behalf are only authorized for the user of the claim.
+        ///
+        /// [`FLOWS_JWT_ENFORCED`]: crate::FLOWS_JWT_ENFORCED
+        pub fn required(self) -> Result<FlowsClaim, (StatusCode, String)> {
+            self.0.ok_or((
+                StatusCode::UNAUTHORIZED,
+                String::from("Missing flows token"),
+            ))
+        }
+    }
+
+    impl FlowsClaim {
+        /// Whether the claim allows acting on behalf of the flow.
+        pub fn authorize(
+            &self,
+            flows_user: &str,
+            flow_id: &str,
+        ) -> Result<(), (StatusCode, String)> {
+            let flow_matches = match self.flow_id {
+                Some(ref f) => f == flow_id,
+                None => true,
+            };
+
+            match self.flows_user == flows_user && flow_matches {
+                true => Ok(()),
+                false => Err((StatusCode::FORBIDDEN, String::from("Not authorized flow"))),
+            }
+        }
+    }
+}
+
+pub mod permission {
+    use reqwest::StatusCode;
+    use sqlx::{PgPool, Postgres};
+
+    use crate::{
+        model::{DiscordChannel, GuildAuthor},
+        shared::get_client,
+        DEFAULT_TOKEN,
+    };
+
+    pub async fn authorized_guild(flows_user: &str, guild_id: &str, pool: &PgPool) -> bool {
+        let sql = "
+            SELECT * FROM guild_author
+            WHERE flows_user = $1 AND discord_guild_id = $2
+        ";
+
+        sqlx::query_as::<Postgres, GuildAuthor>(sql)
+            .bind(flows_user)
+            .bind(guild_id)
+            .fetch_optional(pool)
+            .await
+            .unwrap_or_default()
+            .is_some()
+    }
+
+    pub async fn authorized_channel(
+        flows_user: &str,
+        channel_id: &str,
+        pool: &PgPool,
+    ) -> Result<bool, (StatusCode, String)> {
+        let channel = get_channel(&channel_id).await?;
+        let (sql, id) = match channel.guild_id {
+            Some(gid) => (
+                "SELECT * FROM guild_author
+                WHERE flows_user = $1 AND discord_guild_id = $2
+                ",
+                gid,
+            ),
+            None => match channel.owner_id {
+                Some(oid) => (
+                    "SELECT * FROM guild_author
+                    WHERE flows_user = $1 AND discord_user_id = $2
+                    ",
+                    oid,
+                ),
+                None => {
+                    return Ok(false);
+                }
+            },
+        };
+
+        Ok(sqlx::query_as::<Postgres, GuildAuthor>(sql)
+            .bind(flows_user)
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .unwrap_or_default()
+            .is_some())
+    }
+
+    pub async fn get_channel(channel_id: &str) -> Result<DiscordChannel, (StatusCode, String)> {
+        let url = format!("https://discord.com/api/channels/{}", channel_id);
+
+        let client = get_client();
+        let resp = client
+            .get(url)
+            .header("Authorization", &format!("Bot {}", &*DEFAULT_TOKEN))
+            .send()
+            .await;
+
+        match resp {
+            Ok(r) => match r.status().is_success() {
+                true => r
+                    .json::<DiscordChannel>()
+                    .await
+                    .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string())),
+                false => Err((r.status(), r.text().await.unwrap_or_else(|e| e.to_string()))),
+            },
+            Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        }
+    }
 }
 
 pub mod database {
     use reqwest::StatusCode;
     use sqlx::PgPool;
 
-    use crate::{model::Count, shared::shard_map, DEFAULT_BOT_PLACEHOLDER};
+    use super::crypto::{encrypt, fingerprint};
+    use crate::{
+        model::{Bot, Count},
+        shared::shard_map,
+        DEFAULT_BOT_PLACEHOLDER,
+    };
 
     pub async fn del_listener_by_token(
         flow_id: &str,
         flows_user: &str,
-        channel_id: &str,
-        bot_token: &str,
+        bot_fingerprint: &str,
+        pool: &PgPool,
+    ) -> Result<StatusCode, String> {
+        let delete = "
+            DELETE FROM listener
+            WHERE flow_id = $1 AND flows_user = $2 AND bot_fingerprint = $3
+        ";
+        sqlx::query(delete)
+            .bind(flow_id)
+            .bind(flows_user)
+            .bind(bot_fingerprint)
+            .execute(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(StatusCode::OK)
+    }
+
+    pub async fn del_listeners(
+        flow_id: &str,
+        flows_user: &str,
         pool: &PgPool,
     ) -> Result<StatusCode, String> {
         let delete = "
             DELETE FROM listener
-            WHERE flow_id = $1 AND flows_user = $2 AND channel_id = $3 AND bot_token = $4
+            WHERE flow_id = $1 AND flows_user = $2
         ";
         sqlx::query(delete)
             .bind(flow_id)
             .bind(flows_user)
-            .bind(channel_id)
-            .bind(bot_token)
             .execute(pool)
             .await
             .map_err(|e| e.to_string())?;
@@ -51,35 +385,103 @@ pub mod database {
         Ok(StatusCode::OK)
     }
 
-    pub async fn safe_shutdown(bot_token: &str, pool: &PgPool) {
+    pub async fn safe_shutdown(bot_fingerprint: &str, pool: &PgPool) {
         // Don't shutdown the default Bot
-        if bot_token == DEFAULT_BOT_PLACEHOLDER {
+        if bot_fingerprint == DEFAULT_BOT_PLACEHOLDER {
             return;
         }
-        if is_token_dangling(bot_token, pool).await.unwrap_or(false) {
-            shutdown(bot_token).await;
+        if is_token_dangling(bot_fingerprint, pool)
+            .await
+            .unwrap_or(false)
+        {
+            shutdown(bot_fingerprint).await;
         }
     }
 
-    pub async fn is_token_dangling(bot_token: &str, pool: &PgPool) -> Result<bool, String> {
-        let select: Count =
-            sqlx::query_as("SELECT COUNT(bot_token) FROM listener WHERE bot_token = $1")
-                .bind(bot_token)
-                .fetch_one(pool)
-                .await
-                .map_err(|e| e.to_string())
-                .unwrap();
+    pub async fn is_token_dangling(bot_fingerprint: &str, pool: &PgPool) -> Result<bool, String> {
+        let select: Count = sqlx::query_as(
+            "SELECT COUNT(bot_fingerprint) FROM listener WHERE bot_fingerprint = $1",
+        )
+        .bind(bot_fingerprint)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())
+        .unwrap();
 
         Ok(select.count == 0)
     }
 
-    pub async fn shutdown(bot_token: &str) {
-        let mut guard = shard_map().lock().await;
-        let v = guard.remove(bot_token);
+    pub async fn shutdown(bot_fingerprint: &str) {
+        let removed = shard_map().lock().await.remove(bot_fingerprint);
+
+        if let Some(bot) = removed {
+            bot.shutdown().await;
+        }
+    }
+
+    /// Record the error the client of the bot stopped on.
+    pub async fn record_bot_error(bot_fingerprint: &str, error: &str, fatal: bool, pool: &PgPool) {
+        let upsert = "
+            INSERT INTO bot_status(bot_fingerprint, last_error, fatal)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (bot_fingerprint) DO UPDATE
+            SET last_error = $2, fatal = $3, failed_at = now()
+        ";
+        if let Err(e) = sqlx::query(upsert)
+            .bind(bot_fingerprint)
+            .bind(error)
+            .bind(fatal)
+            .execute(pool)
+            .await
+        {
+            log::error!(
+                "Failed to record the error of bot {}: {}",
+                bot_fingerprint,
+                e
+            );
+        }
+    }
+
+    /// Forget the fatal error of the bot, its client being started again.
+    pub async fn clear_fatal_bot_error(bot_fingerprint: &str, pool: &PgPool) {
+        let update = "
+            UPDATE bot_status SET fatal = false
+            WHERE bot_fingerprint = $1
+        ";
+        _ = sqlx::query(update)
+            .bind(bot_fingerprint)
+            .execute(pool)
+            .await;
+    }
+
+    /// Encrypt the tokens stored in plain text before they were encrypted at
+    /// rest.
+    pub async fn encrypt_plain_tokens(pool: &PgPool) -> Result<(), String> {
+        let select = "
+            SELECT DISTINCT bot_token
+            FROM listener
+            WHERE bot_fingerprint IS NULL
+        ";
+        let bots: Vec<Bot> = sqlx::query_as(select)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
 
-        if let Some(shard_manager) = v {
-            shard_manager.lock().await.shutdown_all().await;
+        let update = "
+            UPDATE listener
+            SET bot_token = $1, bot_fingerprint = $2
+            WHERE bot_token = $3 AND bot_fingerprint IS NULL
+        ";
+        for Bot { token } in bots {
+            sqlx::query(update)
+                .bind(encrypt(&token))
+                .bind(fingerprint(&token))
+                .bind(&token)
+                .execute(pool)
+                .await
+                .map_err(|e| e.to_string())?;
         }
-        drop(guard);
+
+        Ok(())
     }
 }
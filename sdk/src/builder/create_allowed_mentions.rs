@@ -0,0 +1,93 @@
+use serde::Serialize;
+
+use super::*;
+
+/// A builder to restrict who is pinged by the mentions of a message.
+///
+/// By default nobody is pinged, the kinds of mentions to allow have to be
+/// set explicitly.
+#[derive(Clone, Debug, Default, Serialize)]
+#[must_use]
+pub struct CreateAllowedMentions {
+    parse: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    users: Vec<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    roles: Vec<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replied_user: Option<bool>,
+}
+
+impl CreateAllowedMentions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ping `@everyone` and `@here`.
+    pub fn everyone(self, allow: bool) -> Self {
+        self.parse("everyone", allow)
+    }
+
+    /// Ping every mentioned user, prefer [`Self::users`] to only ping some
+    /// of them.
+    pub fn all_users(self, allow: bool) -> Self {
+        self.parse("users", allow)
+    }
+
+    /// Ping every mentioned role, prefer [`Self::roles`] to only ping some
+    /// of them.
+    pub fn all_roles(self, allow: bool) -> Self {
+        self.parse("roles", allow)
+    }
+
+    /// Only ping these users, up to 100 of them.
+    pub fn users(mut self, user_ids: impl IntoIterator<Item = u64>) -> Self {
+        self.users = user_ids.into_iter().collect();
+
+        self
+    }
+
+    /// Only ping these roles, up to 100 of them.
+    pub fn roles(mut self, role_ids: impl IntoIterator<Item = u64>) -> Self {
+        self.roles = role_ids.into_iter().collect();
+
+        self
+    }
+
+    /// Ping the author of the message replied to.
+    pub fn replied_user(mut self, replied_user: bool) -> Self {
+        self.replied_user = Some(replied_user);
+
+        self
+    }
+
+    fn parse(mut self, kind: &'static str, allow: bool) -> Self {
+        self.parse.retain(|k| *k != kind);
+        if allow {
+            self.parse.push(kind);
+        }
+
+        self
+    }
+
+    /// Check the allowed mentions against the limits of Discord.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ExceededLimit`] when more than 100 users or roles are
+    /// allowed.
+    ///
+    /// [`Error::ExceededLimit`]: crate::Error::ExceededLimit
+    pub fn check(&self) -> Result<()> {
+        check_count(
+            "allowed mentions users",
+            self.users.len(),
+            ALLOWED_MENTIONS_ID_LIMIT,
+        )?;
+        check_count(
+            "allowed mentions roles",
+            self.roles.len(),
+            ALLOWED_MENTIONS_ID_LIMIT,
+        )
+    }
+}
@@ -0,0 +1,404 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::*;
+use crate::model::{
+    application::command::{CommandOptionType, CommandType},
+    ChannelType, Permissions,
+};
+
+/// A builder for the definition of an application command, a slash command
+/// or a context menu command on users or messages.
+///
+/// The definitions are registered with [`ApplicationCommands`].
+///
+/// # Examples
+///
+/// ```rust
+/// use discord_flows::builder::{CreateCommand, CreateCommandOption};
+/// use discord_flows::model::application::command::CommandOptionType;
+///
+/// let command = CreateCommand::new("weather")
+///     .description("Show the weather of a city")
+///     .description_localized("fr", "Afficher la météo d'une ville")
+///     .add_option(
+///         CreateCommandOption::new(CommandOptionType::String, "city", "The city")
+///             .required(true),
+///     );
+/// ```
+///
+/// [`ApplicationCommands`]: crate::commands::ApplicationCommands
+#[derive(Clone, Debug, Serialize)]
+#[must_use]
+pub struct CreateCommand {
+    #[serde(rename = "type")]
+    kind: CommandType,
+    name: String,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    name_localizations: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    description_localizations: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    options: Vec<CreateCommandOption>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_member_permissions: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dm_permission: Option<bool>,
+}
+
+impl CreateCommand {
+    /// Create a slash command named `name`, 1 to 32 lowercase characters.
+    ///
+    /// A slash command requires a [`description`].
+    ///
+    /// [`description`]: Self::description
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            kind: CommandType::ChatInput,
+            name: name.into(),
+            name_localizations: HashMap::new(),
+            description: None,
+            description_localizations: HashMap::new(),
+            options: vec![],
+            default_member_permissions: None,
+            dm_permission: None,
+        }
+    }
+
+    /// Set the kind of the command, [`CommandType::User`] and
+    /// [`CommandType::Message`] are shown in the context menu of users and
+    /// messages and have no description nor options.
+    pub fn kind(mut self, kind: CommandType) -> Self {
+        self.kind = kind;
+
+        self
+    }
+
+    pub fn name_localized(mut self, locale: impl Into<String>, name: impl Into<String>) -> Self {
+        self.name_localizations.insert(locale.into(), name.into());
+
+        self
+    }
+
+    /// Set the description of a slash command, 1 to 100 characters.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+
+        self
+    }
+
+    pub fn description_localized(
+        mut self,
+        locale: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        self.description_localizations
+            .insert(locale.into(), description.into());
+
+        self
+    }
+
+    /// Add an option, up to 25 of them.
+    pub fn add_option(mut self, option: CreateCommandOption) -> Self {
+        self.options.push(option);
+
+        self
+    }
+
+    /// Only allow the members having these permissions to use the command,
+    /// an empty set only allows the administrators.
+    pub fn default_member_permissions(mut self, permissions: Permissions) -> Self {
+        self.default_member_permissions = Some(permissions.bits().to_string());
+
+        self
+    }
+
+    /// Set whether the command can be used in direct messages.
+    pub fn dm_permission(mut self, dm_permission: bool) -> Self {
+        self.dm_permission = Some(dm_permission);
+
+        self
+    }
+
+    /// Check the command against the limits of Discord.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ExceededLimit`] or [`Error::NotInRange`] naming the
+    /// first limit exceeded, or [`Error::NotAllowed`] when a context menu
+    /// command has a description or options.
+    ///
+    /// [`Error::ExceededLimit`]: crate::Error::ExceededLimit
+    /// [`Error::NotInRange`]: crate::Error::NotInRange
+    /// [`Error::NotAllowed`]: crate::Error::NotAllowed
+    pub fn check(&self) -> Result<()> {
+        check_name("command name", &self.name)?;
+        for name in self.name_localizations.values() {
+            check_name("command name", name)?;
+        }
+
+        match self.kind {
+            CommandType::ChatInput => {
+                let description = self.description.as_deref().unwrap_or_default();
+                check_description("command description", description)?;
+            }
+            // Context menu commands take neither a description nor options
+            _ => {
+                if self.description.as_deref().is_some_and(|d| !d.is_empty()) {
+                    return Err(Error::NotAllowed("context menu command description"));
+                }
+                if !self.options.is_empty() {
+                    return Err(Error::NotAllowed("context menu command options"));
+                }
+            }
+        }
+        for description in self.description_localizations.values() {
+            check_description("command description", description)?;
+        }
+
+        check_count("command options", self.options.len(), COMMAND_OPTION_LIMIT)?;
+        for option in &self.options {
+            option.check()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A builder for an option of a [`CreateCommand`], or of a sub command.
+#[derive(Clone, Debug, Serialize)]
+#[must_use]
+pub struct CreateCommandOption {
+    #[serde(rename = "type")]
+    kind: CommandOptionType,
+    name: String,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    name_localizations: HashMap<String, String>,
+    description: String,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    description_localizations: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    required: Option<bool>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    choices: Vec<Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    options: Vec<CreateCommandOption>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    channel_types: Vec<ChannelType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_value: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_value: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_length: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_length: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    autocomplete: Option<bool>,
+}
+
+impl CreateCommandOption {
+    /// Create an option named `name`, 1 to 32 lowercase characters, with the
+    /// `description`, 1 to 100 characters.
+    pub fn new(
+        kind: CommandOptionType,
+        name: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        Self {
+            kind,
+            name: name.into(),
+            name_localizations: HashMap::new(),
+            description: description.into(),
+            description_localizations: HashMap::new(),
+            required: None,
+            choices: vec![],
+            options: vec![],
+            channel_types: vec![],
+            min_value: None,
+            max_value: None,
+            min_length: None,
+            max_length: None,
+            autocomplete: None,
+        }
+    }
+
+    pub fn name_localized(mut self, locale: impl Into<String>, name: impl Into<String>) -> Self {
+        self.name_localizations.insert(locale.into(), name.into());
+
+        self
+    }
+
+    pub fn description_localized(
+        mut self,
+        locale: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        self.description_localizations
+            .insert(locale.into(), description.into());
+
+        self
+    }
+
+    pub fn required(mut self, required: bool) -> Self {
+        self.required = Some(required);
+
+        self
+    }
+
+    /// Add a choice to a string option, up to 25 of them. The value is
+    /// limited to 100 characters.
+    pub fn add_string_choice(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.add_choice(name.into(), Value::from(value.into()), HashMap::new())
+    }
+
+    /// Same as [`add_string_choice`] with the localizations of the name.
+    ///
+    /// [`add_string_choice`]: Self::add_string_choice
+    pub fn add_string_choice_localized(
+        self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+        locales: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        let locales = locales
+            .into_iter()
+            .map(|(l, n)| (l.into(), n.into()))
+            .collect();
+        self.add_choice(name.into(), Value::from(value.into()), locales)
+    }
+
+    /// Add a choice to an integer option, up to 25 of them.
+    pub fn add_int_choice(self, name: impl Into<String>, value: i64) -> Self {
+        self.add_choice(name.into(), Value::from(value), HashMap::new())
+    }
+
+    /// Add a choice to a number option, up to 25 of them.
+    pub fn add_number_choice(self, name: impl Into<String>, value: f64) -> Self {
+        self.add_choice(name.into(), Value::from(value), HashMap::new())
+    }
+
+    fn add_choice(mut self, name: String, value: Value, locales: HashMap<String, String>) -> Self {
+        let mut choice = json!({ "name": name, "value": value });
+        if !locales.is_empty() {
+            choice["name_localizations"] = json!(locales);
+        }
+        self.choices.push(choice);
+
+        self
+    }
+
+    /// Add an option to a sub command or a sub command group.
+    pub fn add_sub_option(mut self, option: CreateCommandOption) -> Self {
+        self.options.push(option);
+
+        self
+    }
+
+    /// Restrict the channels selectable by a channel option.
+    pub fn channel_types(mut self, channel_types: impl IntoIterator<Item = ChannelType>) -> Self {
+        self.channel_types = channel_types.into_iter().collect();
+
+        self
+    }
+
+    /// Set the minimum value of an integer or number option.
+    pub fn min_value(mut self, value: impl Into<serde_json::Number>) -> Self {
+        self.min_value = Some(Value::Number(value.into()));
+
+        self
+    }
+
+    /// Set the maximum value of an integer or number option.
+    pub fn max_value(mut self, value: impl Into<serde_json::Number>) -> Self {
+        self.max_value = Some(Value::Number(value.into()));
+
+        self
+    }
+
+    /// Set the minimum length of a string option, up to 6000.
+    pub fn min_length(mut self, min_length: u16) -> Self {
+        self.min_length = Some(min_length);
+
+        self
+    }
+
+    /// Set the maximum length of a string option, between 1 and 6000.
+    pub fn max_length(mut self, max_length: u16) -> Self {
+        self.max_length = Some(max_length);
+
+        self
+    }
+
+    /// Ask for the choices with an [`AutocompleteInteraction`] while the user
+    /// types, instead of giving fixed choices.
+    ///
+    /// [`AutocompleteInteraction`]: crate::model::application::interaction::autocomplete::AutocompleteInteraction
+    pub fn autocomplete(mut self, autocomplete: bool) -> Self {
+        self.autocomplete = Some(autocomplete);
+
+        self
+    }
+
+    fn check(&self) -> Result<()> {
+        check_name("command option name", &self.name)?;
+        for name in self.name_localizations.values() {
+            check_name("command option name", name)?;
+        }
+        check_description("command option description", &self.description)?;
+        for description in self.description_localizations.values() {
+            check_description("command option description", description)?;
+        }
+
+        check_count(
+            "command option choices",
+            self.choices.len(),
+            COMMAND_CHOICE_LIMIT,
+        )?;
+        for choice in &self.choices {
+            let name = choice["name"].as_str().unwrap_or_default();
+            check_range(
+                "command option choice name",
+                name.chars().count() as u64,
+                1,
+                COMMAND_CHOICE_NAME_LIMIT as u64,
+            )?;
+            if let Some(value) = choice["value"].as_str() {
+                check_length(
+                    "command option choice value",
+                    value,
+                    COMMAND_CHOICE_NAME_LIMIT,
+                )?;
+            }
+        }
+
+        let limit = COMMAND_OPTION_LENGTH_LIMIT as u64;
+        if let Some(min_length) = self.min_length {
+            check_range("command option min_length", min_length as u64, 0, limit)?;
+        }
+        if let Some(max_length) = self.max_length {
+            check_range("command option max_length", max_length as u64, 1, limit)?;
+        }
+
+        check_count("command options", self.options.len(), COMMAND_OPTION_LIMIT)?;
+        for option in &self.options {
+            option.check()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn check_name(name: &'static str, value: &str) -> Result<()> {
+    let len = value.chars().count() as u64;
+    check_range(name, len, 1, COMMAND_NAME_LIMIT as u64)
+}
+
+fn check_description(name: &'static str, value: &str) -> Result<()> {
+    let len = value.chars().count() as u64;
+    check_range(name, len, 1, COMMAND_DESCRIPTION_LIMIT as u64)
+}
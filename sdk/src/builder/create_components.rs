@@ -0,0 +1,449 @@
+use serde::Serialize;
+
+use super::*;
+use crate::model::{
+    application::component::{ButtonStyle, InputTextStyle},
+    ReactionType,
+};
+
+/// A builder for an action row, the container of the components of a
+/// message or a modal.
+///
+/// A row holds up to 5 buttons, or a single select menu, or a single text
+/// input.
+#[derive(Clone, Debug, Serialize)]
+#[must_use]
+pub struct CreateActionRow {
+    #[serde(rename = "type")]
+    kind: u8,
+    components: Vec<Component>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+enum Component {
+    Button(CreateButton),
+    SelectMenu(CreateSelectMenu),
+    InputText(CreateInputText),
+}
+
+impl CreateActionRow {
+    pub fn new() -> Self {
+        Self {
+            kind: 1,
+            components: vec![],
+        }
+    }
+
+    pub fn button(mut self, button: CreateButton) -> Self {
+        self.components.push(Component::Button(button));
+
+        self
+    }
+
+    pub fn select_menu(mut self, select_menu: CreateSelectMenu) -> Self {
+        self.components.push(Component::SelectMenu(select_menu));
+
+        self
+    }
+
+    /// Add a text input, only allowed in the rows of a [`CreateModal`].
+    ///
+    /// [`CreateModal`]: super::CreateModal
+    pub fn input_text(mut self, input_text: CreateInputText) -> Self {
+        self.components.push(Component::InputText(input_text));
+
+        self
+    }
+
+    pub(crate) fn has_input_text(&self) -> bool {
+        self.components
+            .iter()
+            .any(|c| matches!(c, Component::InputText(_)))
+    }
+
+    /// Check the row and its components against the limits of Discord.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ExceededLimit`] or [`Error::NotInRange`] naming the
+    /// first limit exceeded.
+    ///
+    /// [`Error::ExceededLimit`]: crate::Error::ExceededLimit
+    /// [`Error::NotInRange`]: crate::Error::NotInRange
+    pub fn check(&self) -> Result<()> {
+        let buttons = self
+            .components
+            .iter()
+            .filter(|c| matches!(c, Component::Button(_)))
+            .count();
+        if buttons == self.components.len() {
+            check_range(
+                "action row components",
+                buttons as u64,
+                1,
+                ACTION_ROW_BUTTON_LIMIT as u64,
+            )?;
+        } else {
+            // Select menus and text inputs take a whole row
+            check_count("action row components", self.components.len(), 1)?;
+        }
+
+        for component in &self.components {
+            match component {
+                Component::Button(b) => b.check()?,
+                Component::SelectMenu(s) => s.check()?,
+                Component::InputText(i) => i.check()?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for CreateActionRow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A builder for a button.
+///
+/// A button either has a `custom_id`, sent back in a
+/// [`MessageComponentInteraction`] when clicked, or is a link opening an URL.
+///
+/// [`MessageComponentInteraction`]: crate::model::application::interaction::message_component::MessageComponentInteraction
+#[derive(Clone, Debug, Serialize)]
+#[must_use]
+pub struct CreateButton {
+    #[serde(rename = "type")]
+    kind: u8,
+    style: ButtonStyle,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    emoji: Option<ReactionType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    custom_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    disabled: bool,
+}
+
+impl CreateButton {
+    /// Create a primary button with the `custom_id`, up to 100 characters.
+    pub fn new(custom_id: impl Into<String>) -> Self {
+        Self {
+            kind: 2,
+            style: ButtonStyle::Primary,
+            label: None,
+            emoji: None,
+            custom_id: Some(custom_id.into()),
+            url: None,
+            disabled: false,
+        }
+    }
+
+    /// Create a button opening the `url` when clicked.
+    pub fn new_link(url: impl Into<String>) -> Self {
+        Self {
+            kind: 2,
+            style: ButtonStyle::Link,
+            label: None,
+            emoji: None,
+            custom_id: None,
+            url: Some(url.into()),
+            disabled: false,
+        }
+    }
+
+    /// Set the style of a non-link button.
+    pub fn style(mut self, style: ButtonStyle) -> Self {
+        if self.url.is_none() {
+            self.style = style;
+        }
+
+        self
+    }
+
+    /// Set the label of the button, up to 80 characters.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+
+        self
+    }
+
+    pub fn emoji(mut self, emoji: impl Into<ReactionType>) -> Self {
+        self.emoji = Some(emoji.into());
+
+        self
+    }
+
+    pub fn disabled(mut self, disabled: bool) -> Self {
+        self.disabled = disabled;
+
+        self
+    }
+
+    fn check(&self) -> Result<()> {
+        if let Some(ref label) = self.label {
+            check_length("button label", label, BUTTON_LABEL_LIMIT)?;
+        }
+        if let Some(ref custom_id) = self.custom_id {
+            check_length("button custom_id", custom_id, CUSTOM_ID_LIMIT)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A builder for a string select menu.
+#[derive(Clone, Debug, Serialize)]
+#[must_use]
+pub struct CreateSelectMenu {
+    #[serde(rename = "type")]
+    kind: u8,
+    custom_id: String,
+    options: Vec<CreateSelectMenuOption>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    placeholder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_values: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_values: Option<u64>,
+    disabled: bool,
+}
+
+impl CreateSelectMenu {
+    /// Create a select menu with the `custom_id`, up to 100 characters, and
+    /// its options, between 1 and 25 of them.
+    pub fn new(
+        custom_id: impl Into<String>,
+        options: impl IntoIterator<Item = CreateSelectMenuOption>,
+    ) -> Self {
+        Self {
+            kind: 3,
+            custom_id: custom_id.into(),
+            options: options.into_iter().collect(),
+            placeholder: None,
+            min_values: None,
+            max_values: None,
+            disabled: false,
+        }
+    }
+
+    /// Set the text shown when nothing is selected, up to 150 characters.
+    pub fn placeholder(mut self, placeholder: impl Into<String>) -> Self {
+        self.placeholder = Some(placeholder.into());
+
+        self
+    }
+
+    /// Set the minimum number of options to select, between 0 and 25.
+    pub fn min_values(mut self, min_values: u64) -> Self {
+        self.min_values = Some(min_values);
+
+        self
+    }
+
+    /// Set the maximum number of options to select, between 1 and 25.
+    pub fn max_values(mut self, max_values: u64) -> Self {
+        self.max_values = Some(max_values);
+
+        self
+    }
+
+    pub fn disabled(mut self, disabled: bool) -> Self {
+        self.disabled = disabled;
+
+        self
+    }
+
+    fn check(&self) -> Result<()> {
+        let limit = SELECT_MENU_OPTION_LIMIT as u64;
+
+        check_length("select menu custom_id", &self.custom_id, CUSTOM_ID_LIMIT)?;
+        check_range("select menu options", self.options.len() as u64, 1, limit)?;
+        if let Some(ref placeholder) = self.placeholder {
+            check_length(
+                "select menu placeholder",
+                placeholder,
+                SELECT_MENU_PLACEHOLDER_LIMIT,
+            )?;
+        }
+        if let Some(min_values) = self.min_values {
+            check_range("select menu min_values", min_values, 0, limit)?;
+        }
+        if let Some(max_values) = self.max_values {
+            check_range("select menu max_values", max_values, 1, limit)?;
+        }
+
+        for option in &self.options {
+            option.check()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A builder for an option of a [`CreateSelectMenu`].
+#[derive(Clone, Debug, Serialize)]
+#[must_use]
+pub struct CreateSelectMenuOption {
+    label: String,
+    value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    emoji: Option<ReactionType>,
+    default: bool,
+}
+
+impl CreateSelectMenuOption {
+    /// Create an option with the `label` shown to the user and the `value`
+    /// sent back when selected, both up to 100 characters.
+    pub fn new(label: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            value: value.into(),
+            description: None,
+            emoji: None,
+            default: false,
+        }
+    }
+
+    /// Set the description of the option, up to 100 characters.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+
+        self
+    }
+
+    pub fn emoji(mut self, emoji: impl Into<ReactionType>) -> Self {
+        self.emoji = Some(emoji.into());
+
+        self
+    }
+
+    /// Select the option by default.
+    pub fn default_selection(mut self, default: bool) -> Self {
+        self.default = default;
+
+        self
+    }
+
+    fn check(&self) -> Result<()> {
+        let limit = SELECT_MENU_OPTION_TEXT_LIMIT;
+
+        check_length("select menu option label", &self.label, limit)?;
+        check_length("select menu option value", &self.value, limit)?;
+        if let Some(ref description) = self.description {
+            check_length("select menu option description", description, limit)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A builder for a text input of a [`CreateModal`].
+///
+/// [`CreateModal`]: super::CreateModal
+#[derive(Clone, Debug, Serialize)]
+#[must_use]
+pub struct CreateInputText {
+    #[serde(rename = "type")]
+    kind: u8,
+    custom_id: String,
+    style: InputTextStyle,
+    label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_length: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_length: Option<u64>,
+    required: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    placeholder: Option<String>,
+}
+
+impl CreateInputText {
+    /// Create a text input with the `custom_id`, up to 100 characters, and
+    /// the `label` shown above it, up to 45 characters.
+    pub fn new(
+        style: InputTextStyle,
+        custom_id: impl Into<String>,
+        label: impl Into<String>,
+    ) -> Self {
+        Self {
+            kind: 4,
+            custom_id: custom_id.into(),
+            style,
+            label: label.into(),
+            min_length: None,
+            max_length: None,
+            required: true,
+            value: None,
+            placeholder: None,
+        }
+    }
+
+    /// Set the minimum length of the input, between 0 and 4000.
+    pub fn min_length(mut self, min_length: u64) -> Self {
+        self.min_length = Some(min_length);
+
+        self
+    }
+
+    /// Set the maximum length of the input, between 1 and 4000.
+    pub fn max_length(mut self, max_length: u64) -> Self {
+        self.max_length = Some(max_length);
+
+        self
+    }
+
+    pub fn required(mut self, required: bool) -> Self {
+        self.required = required;
+
+        self
+    }
+
+    /// Pre-fill the input, up to 4000 characters.
+    pub fn value(mut self, value: impl Into<String>) -> Self {
+        self.value = Some(value.into());
+
+        self
+    }
+
+    /// Set the text shown when the input is empty, up to 100 characters.
+    pub fn placeholder(mut self, placeholder: impl Into<String>) -> Self {
+        self.placeholder = Some(placeholder.into());
+
+        self
+    }
+
+    fn check(&self) -> Result<()> {
+        let limit = INPUT_TEXT_LENGTH_LIMIT as u64;
+
+        check_length("input text custom_id", &self.custom_id, CUSTOM_ID_LIMIT)?;
+        check_length("input text label", &self.label, INPUT_TEXT_LABEL_LIMIT)?;
+        if let Some(min_length) = self.min_length {
+            check_range("input text min_length", min_length, 0, limit)?;
+        }
+        if let Some(max_length) = self.max_length {
+            check_range("input text max_length", max_length, 1, limit)?;
+        }
+        if let Some(ref value) = self.value {
+            check_length("input text value", value, INPUT_TEXT_LENGTH_LIMIT)?;
+        }
+        if let Some(ref placeholder) = self.placeholder {
+            check_length(
+                "input text placeholder",
+                placeholder,
+                INPUT_TEXT_PLACEHOLDER_LIMIT,
+            )?;
+        }
+
+        Ok(())
+    }
+}
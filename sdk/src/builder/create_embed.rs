@@ -0,0 +1,245 @@
+use serde::Serialize;
+
+use super::*;
+use crate::model::Timestamp;
+
+/// A builder to create a rich embed, sent along a message.
+///
+/// See [Discord's documentation] for the limits checked when building it.
+///
+/// [Discord's documentation]: https://discord.com/developers/docs/resources/channel#embed-object-embed-limits
+#[derive(Clone, Debug, Default, Serialize)]
+#[must_use]
+pub struct CreateEmbed {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<Timestamp>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    footer: Option<CreateEmbedFooter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<EmbedUrl>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thumbnail: Option<EmbedUrl>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author: Option<CreateEmbedAuthor>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<EmbedField>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct EmbedUrl {
+    url: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct EmbedField {
+    name: String,
+    value: String,
+    inline: bool,
+}
+
+impl CreateEmbed {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the title of the embed, up to 256 characters.
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+
+        self
+    }
+
+    /// Set the description of the embed, up to 4096 characters.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+
+        self
+    }
+
+    /// Set the URL the title links to.
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+
+        self
+    }
+
+    pub fn timestamp(mut self, timestamp: impl Into<Timestamp>) -> Self {
+        self.timestamp = Some(timestamp.into());
+
+        self
+    }
+
+    /// Set the color of the left border, as `0xRRGGBB`.
+    pub fn color(mut self, color: u32) -> Self {
+        self.color = Some(color);
+
+        self
+    }
+
+    pub fn footer(mut self, footer: CreateEmbedFooter) -> Self {
+        self.footer = Some(footer);
+
+        self
+    }
+
+    pub fn image(mut self, url: impl Into<String>) -> Self {
+        self.image = Some(EmbedUrl { url: url.into() });
+
+        self
+    }
+
+    pub fn thumbnail(mut self, url: impl Into<String>) -> Self {
+        self.thumbnail = Some(EmbedUrl { url: url.into() });
+
+        self
+    }
+
+    pub fn author(mut self, author: CreateEmbedAuthor) -> Self {
+        self.author = Some(author);
+
+        self
+    }
+
+    /// Add a field, up to 25 of them. The name is limited to 256 characters
+    /// and the value to 1024.
+    pub fn field(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+        inline: bool,
+    ) -> Self {
+        self.fields.push(EmbedField {
+            name: name.into(),
+            value: value.into(),
+            inline,
+        });
+
+        self
+    }
+
+    /// Add several fields at once, as `(name, value, inline)`.
+    pub fn fields<N, V>(mut self, fields: impl IntoIterator<Item = (N, V, bool)>) -> Self
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        for (name, value, inline) in fields {
+            self = self.field(name, value, inline);
+        }
+
+        self
+    }
+
+    /// The number of characters counting towards the 6000 characters limit
+    /// of the embeds of a message.
+    pub fn length(&self) -> usize {
+        let len = |s: &Option<String>| s.as_deref().map_or(0, |s| s.chars().count());
+
+        len(&self.title)
+            + len(&self.description)
+            + self.footer.as_ref().map_or(0, |f| f.text.chars().count())
+            + self.author.as_ref().map_or(0, |a| a.name.chars().count())
+            + self
+                .fields
+                .iter()
+                .map(|f| f.name.chars().count() + f.value.chars().count())
+                .sum::<usize>()
+    }
+
+    /// Check the embed against the limits of Discord.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ExceededLimit`] naming the first limit exceeded.
+    ///
+    /// [`Error::ExceededLimit`]: crate::Error::ExceededLimit
+    pub fn check(&self) -> Result<()> {
+        if let Some(ref title) = self.title {
+            check_length("embed title", title, EMBED_TITLE_LIMIT)?;
+        }
+        if let Some(ref description) = self.description {
+            check_length("embed description", description, EMBED_DESCRIPTION_LIMIT)?;
+        }
+        if let Some(ref footer) = self.footer {
+            check_length("embed footer text", &footer.text, EMBED_FOOTER_LIMIT)?;
+        }
+        if let Some(ref author) = self.author {
+            check_length("embed author name", &author.name, EMBED_AUTHOR_LIMIT)?;
+        }
+
+        check_count("embed fields", self.fields.len(), EMBED_FIELD_LIMIT)?;
+        for field in &self.fields {
+            check_length("embed field name", &field.name, EMBED_FIELD_NAME_LIMIT)?;
+            check_length("embed field value", &field.value, EMBED_FIELD_VALUE_LIMIT)?;
+        }
+
+        check_count("embed", self.length(), EMBED_TOTAL_LIMIT)
+    }
+}
+
+/// A builder for the footer of an [`CreateEmbed`], its text is limited to
+/// 2048 characters.
+#[derive(Clone, Debug, Serialize)]
+#[must_use]
+pub struct CreateEmbedFooter {
+    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon_url: Option<String>,
+}
+
+impl CreateEmbedFooter {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            icon_url: None,
+        }
+    }
+
+    pub fn icon_url(mut self, icon_url: impl Into<String>) -> Self {
+        self.icon_url = Some(icon_url.into());
+
+        self
+    }
+}
+
+/// A builder for the author of an [`CreateEmbed`], its name is limited to
+/// 256 characters.
+#[derive(Clone, Debug, Serialize)]
+#[must_use]
+pub struct CreateEmbedAuthor {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon_url: Option<String>,
+}
+
+impl CreateEmbedAuthor {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            url: None,
+            icon_url: None,
+        }
+    }
+
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+
+        self
+    }
+
+    pub fn icon_url(mut self, icon_url: impl Into<String>) -> Self {
+        self.icon_url = Some(icon_url.into());
+
+        self
+    }
+}
@@ -0,0 +1,217 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use super::*;
+
+/// A builder to create the payload of a message, used to send a message,
+/// edit it, or reply to an interaction.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use discord_flows::builder::{CreateAllowedMentions, CreateMessage};
+///
+/// # async fn run(http: discord_flows::http::Http) -> discord_flows::Result<()> {
+/// let message = CreateMessage::new()
+///     .content("Hello <@123456>")
+///     .allowed_mentions(CreateAllowedMentions::new().users([123456]));
+///
+/// http.send_message(123456, &message.build()?).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, Serialize)]
+#[must_use]
+pub struct CreateMessage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tts: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    embeds: Option<Vec<CreateEmbed>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allowed_mentions: Option<CreateAllowedMentions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    components: Option<Vec<CreateActionRow>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message_reference: Option<CreateMessageReference>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    flags: Option<u64>,
+}
+
+const SUPPRESS_EMBEDS: u64 = 1 << 2;
+const EPHEMERAL: u64 = 1 << 6;
+
+impl CreateMessage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the content of the message, up to 2000 characters.
+    pub fn content(mut self, content: impl Into<String>) -> Self {
+        self.content = Some(content.into());
+
+        self
+    }
+
+    pub fn tts(mut self, tts: bool) -> Self {
+        self.tts = Some(tts);
+
+        self
+    }
+
+    /// Add an embed, up to 10 of them.
+    pub fn embed(mut self, embed: CreateEmbed) -> Self {
+        self.embeds.get_or_insert_with(Vec::new).push(embed);
+
+        self
+    }
+
+    /// Set the embeds of the message, an empty list removes the embeds when
+    /// editing a message.
+    pub fn embeds(mut self, embeds: impl IntoIterator<Item = CreateEmbed>) -> Self {
+        self.embeds = Some(embeds.into_iter().collect());
+
+        self
+    }
+
+    pub fn allowed_mentions(mut self, allowed_mentions: CreateAllowedMentions) -> Self {
+        self.allowed_mentions = Some(allowed_mentions);
+
+        self
+    }
+
+    /// Add a row of components, up to 5 of them.
+    pub fn action_row(mut self, row: CreateActionRow) -> Self {
+        self.components.get_or_insert_with(Vec::new).push(row);
+
+        self
+    }
+
+    /// Set the rows of components of the message, an empty list removes the
+    /// components when editing a message.
+    pub fn components(mut self, rows: impl IntoIterator<Item = CreateActionRow>) -> Self {
+        self.components = Some(rows.into_iter().collect());
+
+        self
+    }
+
+    /// Reply to a message.
+    pub fn reference_message(mut self, reference: CreateMessageReference) -> Self {
+        self.message_reference = Some(reference);
+
+        self
+    }
+
+    /// Only show the message to the user of the interaction replied to.
+    pub fn ephemeral(self, ephemeral: bool) -> Self {
+        self.flag(EPHEMERAL, ephemeral)
+    }
+
+    /// Do not show the embeds of the links in the content.
+    pub fn suppress_embeds(self, suppress: bool) -> Self {
+        self.flag(SUPPRESS_EMBEDS, suppress)
+    }
+
+    fn flag(mut self, flag: u64, set: bool) -> Self {
+        let flags = self.flags.unwrap_or_default();
+        self.flags = Some(if set { flags | flag } else { flags & !flag });
+
+        self
+    }
+
+    /// Check the message against the limits of Discord.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ExceededLimit`] or [`Error::NotInRange`] naming the
+    /// first limit exceeded, or [`Error::NotAllowed`] when a row holds a text
+    /// input.
+    ///
+    /// [`Error::ExceededLimit`]: crate::Error::ExceededLimit
+    /// [`Error::NotInRange`]: crate::Error::NotInRange
+    /// [`Error::NotAllowed`]: crate::Error::NotAllowed
+    pub fn check(&self) -> Result<()> {
+        if let Some(ref content) = self.content {
+            check_length("message content", content, MESSAGE_CONTENT_LIMIT)?;
+        }
+
+        if let Some(ref embeds) = self.embeds {
+            check_count("message embeds", embeds.len(), MESSAGE_EMBED_LIMIT)?;
+            for embed in embeds {
+                embed.check()?;
+            }
+            let total = embeds.iter().map(CreateEmbed::length).sum();
+            check_count("message embeds", total, EMBED_TOTAL_LIMIT)?;
+        }
+
+        if let Some(ref allowed_mentions) = self.allowed_mentions {
+            allowed_mentions.check()?;
+        }
+
+        if let Some(ref rows) = self.components {
+            check_count("message components", rows.len(), ACTION_ROW_LIMIT)?;
+            for row in rows {
+                if row.has_input_text() {
+                    return Err(Error::NotAllowed("message input texts"));
+                }
+                row.check()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the message then turn it into the JSON object taken by the
+    /// [`Http`] methods.
+    ///
+    /// [`Http`]: crate::http::Http
+    pub fn build(&self) -> Result<Value> {
+        self.check()?;
+
+        Ok(serde_json::to_value(self)?)
+    }
+}
+
+/// A builder for the reference of a reply to a message.
+#[derive(Clone, Debug, Serialize)]
+#[must_use]
+pub struct CreateMessageReference {
+    message_id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    channel_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guild_id: Option<u64>,
+    fail_if_not_exists: bool,
+}
+
+impl CreateMessageReference {
+    pub fn new(message_id: u64) -> Self {
+        Self {
+            message_id,
+            channel_id: None,
+            guild_id: None,
+            fail_if_not_exists: true,
+        }
+    }
+
+    pub fn channel_id(mut self, channel_id: u64) -> Self {
+        self.channel_id = Some(channel_id);
+
+        self
+    }
+
+    pub fn guild_id(mut self, guild_id: u64) -> Self {
+        self.guild_id = Some(guild_id);
+
+        self
+    }
+
+    /// Send the message as a normal message when the message replied to does
+    /// not exist anymore, instead of failing.
+    pub fn fail_if_not_exists(mut self, fail: bool) -> Self {
+        self.fail_if_not_exists = fail;
+
+        self
+    }
+}
@@ -0,0 +1,70 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use super::*;
+
+/// A builder for a modal, shown with [`Http::create_modal_response`].
+///
+/// [`Http::create_modal_response`]: crate::http::Http::create_modal_response
+#[derive(Clone, Debug, Serialize)]
+#[must_use]
+pub struct CreateModal {
+    custom_id: String,
+    title: String,
+    components: Vec<CreateActionRow>,
+}
+
+impl CreateModal {
+    /// Create a modal with the `custom_id`, up to 100 characters, and the
+    /// `title`, up to 45 characters.
+    pub fn new(custom_id: impl Into<String>, title: impl Into<String>) -> Self {
+        Self {
+            custom_id: custom_id.into(),
+            title: title.into(),
+            components: vec![],
+        }
+    }
+
+    /// Add a row holding a single text input, up to 5 of them.
+    pub fn input_text(mut self, input_text: CreateInputText) -> Self {
+        self.components
+            .push(CreateActionRow::new().input_text(input_text));
+
+        self
+    }
+
+    /// Check the modal against the limits of Discord.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ExceededLimit`] or [`Error::NotInRange`] naming the
+    /// first limit exceeded.
+    ///
+    /// [`Error::ExceededLimit`]: crate::Error::ExceededLimit
+    /// [`Error::NotInRange`]: crate::Error::NotInRange
+    pub fn check(&self) -> Result<()> {
+        check_length("modal custom_id", &self.custom_id, CUSTOM_ID_LIMIT)?;
+        check_length("modal title", &self.title, MODAL_TITLE_LIMIT)?;
+        check_range(
+            "modal components",
+            self.components.len() as u64,
+            1,
+            ACTION_ROW_LIMIT as u64,
+        )?;
+        for row in &self.components {
+            row.check()?;
+        }
+
+        Ok(())
+    }
+
+    /// Check the modal then turn it into the JSON object taken by
+    /// [`Http::create_modal_response`].
+    ///
+    /// [`Http::create_modal_response`]: crate::http::Http::create_modal_response
+    pub fn build(&self) -> Result<Value> {
+        self.check()?;
+
+        Ok(serde_json::to_value(self)?)
+    }
+}
@@ -0,0 +1,221 @@
+//! A set of builders used to make the payloads of the [`Http`] methods taking
+//! a raw JSON body, such as [`Http::send_message`] or
+//! [`Http::create_interaction_response`], and the definitions of the
+//! application commands.
+//!
+//! Every builder checks the limits documented by Discord when it is built, so
+//! a payload that would be rejected returns an [`Error::ExceededLimit`], an
+//! [`Error::NotInRange`] or an [`Error::NotAllowed`] before any request is
+//! sent.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use discord_flows::builder::{CreateActionRow, CreateButton, CreateEmbed, CreateMessage};
+//!
+//! # async fn run(http: discord_flows::http::Http) -> discord_flows::Result<()> {
+//! let message = CreateMessage::new()
+//!     .content("A new ticket was opened")
+//!     .embed(
+//!         CreateEmbed::new()
+//!             .title("Ticket #42")
+//!             .field("Priority", "High", true),
+//!     )
+//!     .action_row(CreateActionRow::new().button(CreateButton::new("close").label("Close")));
+//!
+//! http.send_message(123456, &message.build()?).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Http`]: crate::http::Http
+//! [`Http::send_message`]: crate::http::Http::send_message
+//! [`Http::create_interaction_response`]: crate::http::Http::create_interaction_response
+//! [`Error::ExceededLimit`]: crate::Error::ExceededLimit
+//! [`Error::NotInRange`]: crate::Error::NotInRange
+//! [`Error::NotAllowed`]: crate::Error::NotAllowed
+
+mod create_allowed_mentions;
+mod create_command;
+mod create_components;
+mod create_embed;
+mod create_message;
+mod create_modal;
+
+pub use self::create_allowed_mentions::*;
+pub use self::create_command::*;
+pub use self::create_components::*;
+pub use self::create_embed::*;
+pub use self::create_message::*;
+pub use self::create_modal::*;
+
+use crate::{Error, Result};
+
+/// The maximum number of characters in the content of a message.
+pub const MESSAGE_CONTENT_LIMIT: u32 = 2000;
+/// The maximum number of embeds in a message.
+pub const MESSAGE_EMBED_LIMIT: u32 = 10;
+/// The maximum number of characters in all the embeds of a message.
+pub const EMBED_TOTAL_LIMIT: u32 = 6000;
+pub const EMBED_TITLE_LIMIT: u32 = 256;
+pub const EMBED_DESCRIPTION_LIMIT: u32 = 4096;
+pub const EMBED_FIELD_LIMIT: u32 = 25;
+pub const EMBED_FIELD_NAME_LIMIT: u32 = 256;
+pub const EMBED_FIELD_VALUE_LIMIT: u32 = 1024;
+pub const EMBED_FOOTER_LIMIT: u32 = 2048;
+pub const EMBED_AUTHOR_LIMIT: u32 = 256;
+/// The maximum number of action rows in a message or a modal.
+pub const ACTION_ROW_LIMIT: u32 = 5;
+/// The maximum number of buttons in an action row.
+pub const ACTION_ROW_BUTTON_LIMIT: u32 = 5;
+pub const CUSTOM_ID_LIMIT: u32 = 100;
+pub const BUTTON_LABEL_LIMIT: u32 = 80;
+pub const SELECT_MENU_OPTION_LIMIT: u32 = 25;
+pub const SELECT_MENU_PLACEHOLDER_LIMIT: u32 = 150;
+pub const SELECT_MENU_OPTION_TEXT_LIMIT: u32 = 100;
+pub const INPUT_TEXT_LABEL_LIMIT: u32 = 45;
+pub const INPUT_TEXT_PLACEHOLDER_LIMIT: u32 = 100;
+pub const INPUT_TEXT_LENGTH_LIMIT: u32 = 4000;
+pub const MODAL_TITLE_LIMIT: u32 = 45;
+pub const ALLOWED_MENTIONS_ID_LIMIT: u32 = 100;
+/// The maximum number of commands of each kind in a scope, global or guild.
+pub const COMMAND_LIMIT: u32 = 100;
+pub const COMMAND_NAME_LIMIT: u32 = 32;
+pub const COMMAND_DESCRIPTION_LIMIT: u32 = 100;
+pub const COMMAND_OPTION_LIMIT: u32 = 25;
+pub const COMMAND_CHOICE_LIMIT: u32 = 25;
+pub const COMMAND_CHOICE_NAME_LIMIT: u32 = 100;
+pub const COMMAND_OPTION_LENGTH_LIMIT: u32 = 6000;
+
+/// Returns [`Error::ExceededLimit`] when `value` is longer than `limit`
+/// characters.
+fn check_length(name: &str, value: &str, limit: u32) -> Result<()> {
+    check_count(name, value.chars().count(), limit)
+}
+
+/// Returns [`Error::ExceededLimit`] when `count` is greater than `limit`.
+fn check_count(name: &str, count: usize, limit: u32) -> Result<()> {
+    if count > limit as usize {
+        return Err(Error::ExceededLimit(name.to_string(), limit));
+    }
+
+    Ok(())
+}
+
+fn check_range(name: &'static str, value: u64, min: u64, max: u64) -> Result<()> {
+    if value < min || value > max {
+        return Err(Error::NotInRange(name, value, min, max));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::application::{command::CommandType, component::InputTextStyle};
+
+    fn exceeded(result: Result<impl Sized>) -> (String, u32) {
+        match result {
+            Err(Error::ExceededLimit(name, limit)) => (name, limit),
+            _ => panic!("no limit exceeded"),
+        }
+    }
+
+    fn input_text() -> CreateInputText {
+        CreateInputText::new(InputTextStyle::Short, "name", "Name")
+    }
+
+    #[test]
+    fn counts_the_characters_rather_than_the_bytes() {
+        let content = "é".repeat(MESSAGE_CONTENT_LIMIT as usize);
+        assert!(CreateMessage::new().content(&content).check().is_ok());
+
+        let message = CreateMessage::new().content(content + "é");
+        assert_eq!(
+            exceeded(message.check()),
+            (String::from("message content"), MESSAGE_CONTENT_LIMIT)
+        );
+    }
+
+    #[test]
+    fn limits_the_total_length_of_the_embeds() {
+        let embed = CreateEmbed::new().description("a".repeat(4000));
+        assert!(embed.check().is_ok());
+
+        let message = CreateMessage::new().embeds([embed.clone(), embed]);
+        assert_eq!(
+            exceeded(message.check()),
+            (String::from("message embeds"), EMBED_TOTAL_LIMIT)
+        );
+    }
+
+    #[test]
+    fn limits_the_components_of_a_row() {
+        let row = (0..=ACTION_ROW_BUTTON_LIMIT).fold(CreateActionRow::new(), |row, i| {
+            row.button(CreateButton::new(i.to_string()).label("Button"))
+        });
+        assert!(matches!(
+            row.check(),
+            Err(Error::NotInRange("action row components", 6, 1, 5))
+        ));
+
+        let row = CreateActionRow::new()
+            .button(CreateButton::new("ok").label("Ok"))
+            .select_menu(CreateSelectMenu::new(
+                "menu",
+                [CreateSelectMenuOption::new("A", "a")],
+            ));
+        assert_eq!(
+            exceeded(row.check()),
+            (String::from("action row components"), 1)
+        );
+    }
+
+    #[test]
+    fn rejects_the_input_texts_outside_modals() {
+        assert!(CreateModal::new("form", "Form")
+            .input_text(input_text())
+            .check()
+            .is_ok());
+
+        let message =
+            CreateMessage::new().action_row(CreateActionRow::new().input_text(input_text()));
+        assert!(matches!(
+            message.check(),
+            Err(Error::NotAllowed("message input texts"))
+        ));
+    }
+
+    #[test]
+    fn requires_a_component_in_modals() {
+        assert!(matches!(
+            CreateModal::new("form", "Form").check(),
+            Err(Error::NotInRange("modal components", 0, 1, 5))
+        ));
+    }
+
+    #[test]
+    fn rejects_the_description_of_context_menu_commands() {
+        let command = CreateCommand::new("Report").kind(CommandType::Message);
+        assert!(command.check().is_ok());
+
+        let command = command.description("Report the message");
+        assert!(matches!(
+            command.check(),
+            Err(Error::NotAllowed("context menu command description"))
+        ));
+    }
+
+    #[test]
+    fn limits_the_mentions() {
+        let mentions = CreateAllowedMentions::new().users(0..=ALLOWED_MENTIONS_ID_LIMIT as u64);
+        assert_eq!(
+            exceeded(mentions.check()),
+            (
+                String::from("allowed mentions users"),
+                ALLOWED_MENTIONS_ID_LIMIT
+            )
+        );
+    }
+}
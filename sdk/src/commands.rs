@@ -0,0 +1,416 @@
+//! Declarative registration of the application commands of a bot.
+//!
+//! The commands are declared with [`CreateCommand`] and handed to
+//! [`Bot::listen_with_commands`]. When the flow is registered, the declared
+//! commands are compared with the ones Discord knows and overwritten in bulk
+//! only when they differ, so redeploying a flow does not touch unchanged
+//! commands.
+//!
+//! [`CreateCommand`]: crate::builder::CreateCommand
+//! [`Bot::listen_with_commands`]: crate::Bot::listen_with_commands
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Map, Value};
+
+use crate::{builder::CreateCommand, defer::AutoDefer, http::Http, Error, Result};
+
+/// The application commands of a bot, global or of some guilds.
+///
+/// Only the scopes declared are synchronized, the commands of a guild which
+/// is not declared are left as is.
+///
+/// # Examples
+///
+/// ```rust
+/// use discord_flows::{builder::CreateCommand, commands::ApplicationCommands};
+///
+/// let commands = ApplicationCommands::new().guild(
+///     123456,
+///     [CreateCommand::new("ping").description("Check the bot is alive")],
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+#[must_use]
+pub struct ApplicationCommands {
+    global: Option<Vec<CreateCommand>>,
+    guilds: BTreeMap<u64, Vec<CreateCommand>>,
+    auto_defer: Option<AutoDefer>,
+}
+
+impl ApplicationCommands {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the global commands, available in every guild of the bot and in
+    /// direct messages.
+    ///
+    /// [`DefaultBot`] can not have global commands.
+    ///
+    /// [`DefaultBot`]: crate::DefaultBot
+    pub fn global(mut self, commands: impl IntoIterator<Item = CreateCommand>) -> Self {
+        self.global = Some(commands.into_iter().collect());
+
+        self
+    }
+
+    /// Set the commands of the guild, an empty list removes them all.
+    ///
+    /// With [`DefaultBot`], the guild must have been authorized on
+    /// flows.network. As the bot is shared with the other flows, the
+    /// commands are created or edited one by one, matched by name, and the
+    /// other commands of the guild are left as is: a command no longer
+    /// declared is not removed.
+    ///
+    /// [`DefaultBot`]: crate::DefaultBot
+    pub fn guild(
+        mut self,
+        guild_id: u64,
+        commands: impl IntoIterator<Item = CreateCommand>,
+    ) -> Self {
+        self.guilds.insert(guild_id, commands.into_iter().collect());
+
+        self
+    }
+
+    /// Have the integration defer the interactions of the flow, see
+    /// [`AutoDefer`].
+    pub fn auto_defer(mut self, auto_defer: AutoDefer) -> Self {
+        self.auto_defer = Some(auto_defer);
+
+        self
+    }
+
+    pub(crate) fn auto_defer_setting(&self) -> Option<&AutoDefer> {
+        self.auto_defer.as_ref()
+    }
+
+    /// Check every command against the limits of Discord.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ExceededLimit`], [`Error::NotInRange`] or
+    /// [`Error::NotAllowed`] naming the first limit exceeded.
+    pub fn check(&self) -> Result<()> {
+        for commands in self.global.iter().chain(self.guilds.values()) {
+            for command in commands {
+                command.check()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Overwrite the commands of each declared scope which differ from the
+    /// declaration.
+    ///
+    /// The application id of `http` is fetched first when it is not set.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of [`check`] or of the first failed request, the
+    /// scopes synchronized before it stay synchronized.
+    ///
+    /// [`check`]: Self::check
+    pub async fn sync(&self, http: &Http) -> Result<()> {
+        self.check()?;
+        set_application_id(http).await?;
+
+        if let Some(ref global) = self.global {
+            let declared = declared_commands(global)?;
+            let current = http
+                .get_global_application_commands_with_localizations()
+                .await?;
+            if has_changed(&current, &declared, Scope::Global) {
+                http.create_global_application_commands(&Value::Array(declared))
+                    .await?;
+            }
+        }
+
+        for (guild_id, commands) in &self.guilds {
+            let declared = declared_commands(commands)?;
+            let current = http
+                .get_guild_application_commands_with_localizations(*guild_id)
+                .await?;
+            if has_changed(&current, &declared, Scope::Guild) {
+                http.create_guild_application_commands(*guild_id, &Value::Array(declared))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Same as [`sync`] for the application shared by the flows of
+    /// [`DefaultBot`], whose guild commands are merged by name rather than
+    /// overwritten in bulk.
+    ///
+    /// [`sync`]: Self::sync
+    /// [`DefaultBot`]: crate::DefaultBot
+    pub(crate) async fn sync_shared(&self, http: &Http) -> Result<()> {
+        self.check()?;
+        if self.global.is_some() {
+            return Err(Error::NotAllowed("DefaultBot global commands"));
+        }
+        set_application_id(http).await?;
+
+        for (guild_id, commands) in &self.guilds {
+            let current = http
+                .get_guild_application_commands_with_localizations(*guild_id)
+                .await?;
+            for command in declared_commands(commands)? {
+                if changed_command(&current, &command) {
+                    // Discord replaces the command of the same name
+                    http.create_guild_application_command(*guild_id, &command)
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+async fn set_application_id(http: &Http) -> Result<()> {
+    if http.application_id().is_none() {
+        let application = http.get_current_application_info().await?;
+        http.set_application_id(application.id.0);
+    }
+
+    Ok(())
+}
+
+/// Where the commands are registered, the guild commands are not offered in
+/// direct messages.
+#[derive(Clone, Copy, PartialEq)]
+enum Scope {
+    Global,
+    Guild,
+}
+
+/// The fields of a command compared when synchronizing, the others are set
+/// by Discord.
+const COMMAND_KEYS: &[&str] = &[
+    "type",
+    "name",
+    "name_localizations",
+    "description",
+    "description_localizations",
+    "options",
+    "default_member_permissions",
+];
+const OPTION_KEYS: &[&str] = &[
+    "type",
+    "name",
+    "name_localizations",
+    "description",
+    "description_localizations",
+    "required",
+    "choices",
+    "options",
+    "channel_types",
+    "min_value",
+    "max_value",
+    "min_length",
+    "max_length",
+    "autocomplete",
+];
+const CHOICE_KEYS: &[&str] = &["name", "name_localizations", "value"];
+
+fn declared_commands(commands: &[CreateCommand]) -> Result<Vec<Value>> {
+    let commands = commands
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    for kind in 1..=3 {
+        let count = commands.iter().filter(|c| c["type"] == kind).count();
+        if count > crate::builder::COMMAND_LIMIT as usize {
+            return Err(Error::ExceededLimit(
+                "commands".to_string(),
+                crate::builder::COMMAND_LIMIT,
+            ));
+        }
+    }
+
+    Ok(commands)
+}
+
+fn has_changed(current: &[Value], declared: &[Value], scope: Scope) -> bool {
+    normalize_commands(current, scope) != normalize_commands(declared, scope)
+}
+
+/// Whether the guild command differs from the one of the same kind and name
+/// in `current`, if any.
+fn changed_command(current: &[Value], command: &Value) -> bool {
+    let command = normalize_command(command, Scope::Guild);
+    let same = current
+        .iter()
+        .map(|c| normalize_command(c, Scope::Guild))
+        .find(|c| command_key(c) == command_key(&command));
+
+    same.as_ref() != Some(&command)
+}
+
+/// Strip the commands to the fields they are compared on, without the
+/// fields left to their default value, ordered by kind and name.
+fn normalize_commands(commands: &[Value], scope: Scope) -> Vec<Value> {
+    let mut commands: Vec<Value> = commands
+        .iter()
+        .map(|c| normalize_command(c, scope))
+        .collect();
+    commands.sort_by(|a, b| command_key(a).cmp(&command_key(b)));
+
+    commands
+}
+
+fn command_key(command: &Value) -> (Option<u64>, Option<&str>) {
+    (command["type"].as_u64(), command["name"].as_str())
+}
+
+fn normalize_command(command: &Value, scope: Scope) -> Value {
+    let mut normalized = project(command, COMMAND_KEYS);
+    normalized.entry("type").or_insert_with(|| json!(1));
+    // Only the global commands can be used in direct messages, whatever the
+    // guild commands are declared with
+    if scope == Scope::Global {
+        let dm_permission = command["dm_permission"].as_bool().unwrap_or(true);
+        normalized.insert("dm_permission".to_string(), json!(dm_permission));
+    }
+    // Discord returns the permissions as a string, serenity as a number
+    if let Some(permissions) = normalized.get_mut("default_member_permissions") {
+        if let Some(bits) = permissions.as_u64() {
+            *permissions = json!(bits.to_string());
+        }
+    }
+    normalize_options(&mut normalized);
+
+    Value::Object(normalized)
+}
+
+fn normalize_options(parent: &mut Map<String, Value>) {
+    if let Some(Value::Array(options)) = parent.get_mut("options") {
+        for option in options {
+            let mut normalized = project(option, OPTION_KEYS);
+            for key in ["min_value", "max_value"] {
+                if let Some(value) = normalized.get_mut(key) {
+                    normalize_number(value);
+                }
+            }
+            if let Some(Value::Array(choices)) = normalized.get_mut("choices") {
+                for choice in choices {
+                    let mut choice_normalized = project(choice, CHOICE_KEYS);
+                    if let Some(value) = choice_normalized.get_mut("value") {
+                        normalize_number(value);
+                    }
+                    *choice = Value::Object(choice_normalized);
+                }
+            }
+            normalize_options(&mut normalized);
+            *option = Value::Object(normalized);
+        }
+    }
+}
+
+/// Compare the numbers as floats, `1` and `1.0` being the same bound or
+/// choice whichever way it was declared or returned.
+fn normalize_number(value: &mut Value) {
+    if let Some(n) = value.as_f64() {
+        *value = json!(n);
+    }
+}
+
+/// Keep the `keys` of `value` which are not null, false or empty.
+fn project(value: &Value, keys: &[&str]) -> Map<String, Value> {
+    keys.iter()
+        .filter_map(|k| {
+            let v = value.get(k)?;
+            let empty = match v {
+                Value::Null => true,
+                Value::Bool(b) => !b,
+                Value::String(s) => s.is_empty(),
+                Value::Array(a) => a.is_empty(),
+                Value::Object(o) => o.is_empty(),
+                Value::Number(_) => false,
+            };
+            (!empty).then(|| (k.to_string(), v.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{builder::CreateCommandOption, model::application::command::CommandOptionType};
+
+    fn declared() -> Vec<Value> {
+        let sides = CreateCommandOption::new(CommandOptionType::Integer, "sides", "Sides")
+            .required(true)
+            .min_value(2)
+            .max_value(100)
+            .add_int_choice("d6", 6);
+        let command = CreateCommand::new("roll")
+            .description("Roll a die")
+            .dm_permission(false)
+            .add_option(sides);
+
+        declared_commands(&[command]).unwrap()
+    }
+
+    /// The command as Discord returns it for a guild.
+    fn returned() -> Value {
+        json!({
+            "id": "1",
+            "application_id": "2",
+            "guild_id": "3",
+            "version": "4",
+            "type": 1,
+            "name": "roll",
+            "description": "Roll a die",
+            "default_member_permissions": null,
+            "nsfw": false,
+            "options": [{
+                "type": 4,
+                "name": "sides",
+                "description": "Sides",
+                "required": true,
+                "min_value": 2.0,
+                "max_value": 100,
+                "choices": [{ "name": "d6", "value": 6.0 }],
+            }],
+        })
+    }
+
+    #[test]
+    fn compares_the_guild_commands_as_discord_returns_them() {
+        assert!(!has_changed(&[returned()], &declared(), Scope::Guild));
+
+        let mut renamed = returned();
+        renamed["options"][0]["description"] = json!("Faces");
+        assert!(has_changed(&[renamed], &declared(), Scope::Guild));
+    }
+
+    #[test]
+    fn compares_the_dm_permission_of_the_global_commands() {
+        let mut global = returned();
+        global["dm_permission"] = json!(true);
+        assert!(has_changed(&[global.clone()], &declared(), Scope::Global));
+
+        global["dm_permission"] = json!(false);
+        assert!(!has_changed(&[global], &declared(), Scope::Global));
+    }
+
+    #[test]
+    fn merges_the_shared_commands_by_name() {
+        let other = json!({ "type": 1, "name": "other", "description": "Of another flow" });
+        let command = &declared()[0];
+
+        assert!(!changed_command(&[other.clone(), returned()], command));
+        assert!(changed_command(&[other], command));
+
+        let mut edited = returned();
+        edited["options"][0]["max_value"] = json!(20);
+        assert!(changed_command(&[edited], command));
+    }
+}
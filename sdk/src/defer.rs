@@ -0,0 +1,94 @@
+use serde::Serialize;
+
+use crate::headers_from_subcription;
+
+/// The interactions the integration acknowledges as soon as it receives
+/// them, before the flow is even started, so that Discord's 3 seconds
+/// deadline is always met.
+///
+/// A deferred command or modal shows a "thinking" state until the flow
+/// edits the original response, a deferred component waits for the flow to
+/// edit its message. Use [`Http::send_interaction_message`] and
+/// [`Http::update_interaction_message`] to respond to an interaction whether
+/// it was deferred or not.
+///
+/// # Example
+///
+/// ```rust
+/// use discord_flows::{builder::CreateCommand, commands::ApplicationCommands, defer::AutoDefer};
+///
+/// let commands = ApplicationCommands::new()
+///     .guild(123456, [CreateCommand::new("report").description("Build a report")])
+///     .auto_defer(AutoDefer::new().commands(["report"]).ephemeral(true));
+/// ```
+///
+/// [`Http::send_interaction_message`]: crate::http::Http::send_interaction_message
+/// [`Http::update_interaction_message`]: crate::http::Http::update_interaction_message
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AutoDefer {
+    all: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    commands: Vec<String>,
+    components: bool,
+    modals: bool,
+    ephemeral: bool,
+}
+
+impl AutoDefer {
+    /// Construct a setting which defers nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defer every application command, component and modal interaction.
+    #[must_use]
+    pub fn all(mut self, all: bool) -> Self {
+        self.all = all;
+
+        self
+    }
+
+    /// Defer the application commands with these names.
+    #[must_use]
+    pub fn commands<I, S>(mut self, commands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.commands = commands.into_iter().map(Into::into).collect();
+
+        self
+    }
+
+    /// Defer the interactions of the message components.
+    #[must_use]
+    pub fn components(mut self, components: bool) -> Self {
+        self.components = components;
+
+        self
+    }
+
+    /// Defer the submissions of the modals.
+    #[must_use]
+    pub fn modals(mut self, modals: bool) -> Self {
+        self.modals = modals;
+
+        self
+    }
+
+    /// Only show the response to the commands and modals to their user.
+    #[must_use]
+    pub fn ephemeral(mut self, ephemeral: bool) -> Self {
+        self.ephemeral = ephemeral;
+
+        self
+    }
+}
+
+/// Whether the integration already deferred the interaction being handled.
+pub fn is_deferred() -> bool {
+    headers_from_subcription()
+        .unwrap_or_default()
+        .into_iter()
+        .any(|(k, v)| k.to_lowercase() == "x-discord-deferred" && v == "true")
+}
@@ -0,0 +1,137 @@
+use std::error::Error as StdError;
+use std::fmt::{self, Error as FormatError};
+use std::io::Error as IoError;
+use std::result::Result as StdResult;
+
+use http_req::error::Error as ReqwestError;
+use serde_json::Error as JsonError;
+
+use crate::{http::HttpError, router::CommandError};
+
+/// The common result type between most library functions.
+///
+/// The library exposes functions which, for a result type, exposes only one
+/// type, rather than the usual 2 (`Result<T, Error>`). This is because all
+/// functions that return a result return the crate's [`Error`], so this is
+/// implied, and a "simpler" result is used.
+pub type Result<T> = StdResult<T, Error>;
+
+/// A common error enum returned by most of the library's functionality within a
+/// custom [`Result`].
+///
+/// The most common error types, the [`HttpError`] and [`JsonError`] enums,
+/// are both wrapped around this in the form of [`Self::Http`] and
+/// [`Self::Json`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Input exceeded a limit.
+    ///
+    /// (name, limit)
+    ExceededLimit(String, u32),
+    /// The input is not in the specified range.
+    ///
+    /// (param_name, value, range_min, range_max)
+    NotInRange(&'static str, u64, u64, u64),
+    /// The input is not allowed where it is used, such as a text input in a
+    /// message rather than in a modal.
+    ///
+    /// (name)
+    NotAllowed(&'static str),
+    /// An error while decoding a payload.
+    Format(FormatError),
+    /// An [`std::io`] error.
+    Io(IoError),
+    /// An error from the [`serde_json`] crate.
+    Json(JsonError),
+    /// Some other error. This is only used for "Expected value <TYPE>" errors,
+    /// when a more detailed error can not be easily provided.
+    Other(&'static str),
+    /// An error from the [`url`] crate.
+    Url(String),
+    /// An [http] error, including Discord's structured error response for
+    /// unsuccessful requests.
+    ///
+    /// [http]: crate::http
+    Http(Box<HttpError>),
+    /// An error while routing a command or reading its options.
+    Command(CommandError),
+}
+
+impl Error {
+    /// Returns the [`HttpError`] if this error originates from an HTTP request.
+    #[must_use]
+    pub fn http_error(&self) -> Option<&HttpError> {
+        match self {
+            Self::Http(inner) => Some(inner),
+            _ => None,
+        }
+    }
+
+    /// Returns Discord's JSON error code (e.g. `50013` for "Missing
+    /// Permissions") if this error is an unsuccessful request.
+    #[must_use]
+    pub fn discord_error_code(&self) -> Option<isize> {
+        self.http_error().and_then(HttpError::discord_error_code)
+    }
+}
+
+impl From<FormatError> for Error {
+    fn from(e: FormatError) -> Error {
+        Error::Format(e)
+    }
+}
+
+impl From<IoError> for Error {
+    fn from(e: IoError) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<JsonError> for Error {
+    fn from(e: JsonError) -> Error {
+        Error::Json(e)
+    }
+}
+
+impl From<HttpError> for Error {
+    fn from(e: HttpError) -> Error {
+        Error::Http(Box::new(e))
+    }
+}
+
+impl From<ReqwestError> for Error {
+    fn from(e: ReqwestError) -> Error {
+        HttpError::Request(e).into()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Other(msg) => f.write_str(msg),
+            Error::ExceededLimit(..) => f.write_str("Input exceeded a limit"),
+            Error::NotInRange(..) => f.write_str("Input is not in the specified range"),
+            Error::NotAllowed(..) => f.write_str("Input is not allowed here"),
+            Error::Format(inner) => fmt::Display::fmt(&inner, f),
+            Error::Io(inner) => fmt::Display::fmt(&inner, f),
+            Error::Json(inner) => fmt::Display::fmt(&inner, f),
+            Error::Url(msg) => f.write_str(msg),
+            Error::Http(inner) => fmt::Display::fmt(&inner, f),
+            Error::Command(inner) => fmt::Display::fmt(&inner, f),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Format(inner) => Some(inner),
+            Error::Io(inner) => Some(inner),
+            Error::Json(inner) => Some(inner),
+            Error::Http(inner) => Some(inner),
+            Error::Command(inner) => Some(inner),
+            _ => None,
+        }
+    }
+}
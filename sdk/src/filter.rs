@@ -0,0 +1,115 @@
+use serde::Serialize;
+
+/// A filter registered along with a listener so that only the events a flow
+/// is interested in are delivered to it.
+///
+/// The filter is evaluated by the integration before the flow is triggered,
+/// an event is delivered only when it passes every condition that is set.
+/// Conditions on the content or the mentions only apply to the events that
+/// carry them (`Message` and `MessageUpdate`).
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use discord_flows::{filter::EventFilter, Bot, ProvidedBot};
+///
+/// # async fn run() {
+/// let filter = EventFilter::new()
+///     .event_models(["Message"])
+///     .ignore_bots(true)
+///     .content_prefix("!");
+///
+/// ProvidedBot::new("YOUR BOT TOKEN")
+///     .listen_with_filter(&filter, |msg| async {
+///         todo!()
+///     })
+///     .await;
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct EventFilter {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    event_models: Vec<String>,
+    ignore_bots: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    allowed_authors: Vec<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    denied_authors: Vec<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_regex: Option<String>,
+    mention_only: bool,
+}
+
+impl EventFilter {
+    /// Construct a filter which lets every event through.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only deliver the events of these kinds, named after the variants of
+    /// [`EventModel`].
+    ///
+    /// [`EventModel`]: crate::EventModel
+    #[must_use]
+    pub fn event_models<I, S>(mut self, event_models: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.event_models = event_models.into_iter().map(Into::into).collect();
+
+        self
+    }
+
+    /// Drop the events caused by bots, including the one listened to.
+    #[must_use]
+    pub fn ignore_bots(mut self, ignore_bots: bool) -> Self {
+        self.ignore_bots = ignore_bots;
+
+        self
+    }
+
+    /// Only deliver the events caused by these users.
+    #[must_use]
+    pub fn allowed_authors(mut self, user_ids: impl IntoIterator<Item = u64>) -> Self {
+        self.allowed_authors = user_ids.into_iter().collect();
+
+        self
+    }
+
+    /// Drop the events caused by these users.
+    #[must_use]
+    pub fn denied_authors(mut self, user_ids: impl IntoIterator<Item = u64>) -> Self {
+        self.denied_authors = user_ids.into_iter().collect();
+
+        self
+    }
+
+    /// Only deliver the messages starting with `prefix`.
+    #[must_use]
+    pub fn content_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.content_prefix = Some(prefix.into());
+
+        self
+    }
+
+    /// Only deliver the messages matching the regular expression `regex`.
+    ///
+    /// The expression is validated when the listener is registered.
+    #[must_use]
+    pub fn content_regex(mut self, regex: impl Into<String>) -> Self {
+        self.content_regex = Some(regex.into());
+
+        self
+    }
+
+    /// Only deliver the messages mentioning the bot.
+    #[must_use]
+    pub fn mention_only(mut self, mention_only: bool) -> Self {
+        self.mention_only = mention_only;
+
+        self
+    }
+}
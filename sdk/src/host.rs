@@ -0,0 +1,180 @@
+//! The functions the flows.network runtime provides to the flow.
+//!
+//! In wasm they are imported from the runtime. In native builds they are
+//! backed by the [`TestHost`] installed on the current thread, so that flows
+//! can be driven with plain `cargo test`.
+//!
+//! [`TestHost`]: crate::testing::TestHost
+
+use std::sync::Arc;
+
+use http_req::response::Response;
+
+use crate::http::{TokioTransport, Transport, TransportRequest};
+use crate::Result;
+
+lazy_static::lazy_static! {
+    /// The transport shared by the [`Http`] clients, so that they reuse each
+    /// other's connections.
+    ///
+    /// [`Http`]: crate::http::Http
+    static ref TRANSPORT: TokioTransport = TokioTransport::new();
+}
+
+#[cfg(target_arch = "wasm32")]
+mod ffi {
+    extern "C" {
+        // Flag if current running is for listening(1) or message receving(0)
+        pub fn is_listening() -> i32;
+
+        // Return the user id of the flows platform
+        pub fn get_flows_user(p: *mut u8) -> i32;
+
+        // Return the flow id
+        pub fn get_flow_id(p: *mut u8) -> i32;
+
+        pub fn get_event_body_length() -> i32;
+        pub fn get_event_body(p: *mut u8) -> i32;
+        pub fn get_event_headers_length() -> i32;
+        pub fn get_event_headers(p: *mut u8) -> i32;
+        pub fn set_output(p: *const u8, len: i32);
+        pub fn set_error_code(code: i16);
+    }
+}
+
+/// Whether the flow is run to register its listeners rather than to handle
+/// an event.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn is_listening() -> bool {
+    unsafe { ffi::is_listening() == 1 }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn flows_user() -> String {
+    unsafe {
+        let mut flows_user = Vec::<u8>::with_capacity(100);
+        let c = ffi::get_flows_user(flows_user.as_mut_ptr());
+        flows_user.set_len(c as usize);
+        String::from_utf8(flows_user).unwrap()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn flow_id() -> String {
+    unsafe {
+        let mut flow_id = Vec::<u8>::with_capacity(100);
+        let c = ffi::get_flow_id(flow_id.as_mut_ptr());
+        if c == 0 {
+            panic!("Failed to get flow id");
+        }
+        flow_id.set_len(c as usize);
+        String::from_utf8(flow_id).unwrap()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn event_body() -> Vec<u8> {
+    unsafe {
+        let l = ffi::get_event_body_length();
+        let mut event_body = Vec::<u8>::with_capacity(l as usize);
+        let c = ffi::get_event_body(event_body.as_mut_ptr());
+        assert!(c == l);
+        event_body.set_len(c as usize);
+        event_body
+    }
+}
+
+/// The headers of the event, a JSON array of name and value pairs.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn event_headers() -> Vec<u8> {
+    unsafe {
+        let l = ffi::get_event_headers_length();
+        let mut event_headers = Vec::<u8>::with_capacity(l as usize);
+        let c = ffi::get_event_headers(event_headers.as_mut_ptr());
+        assert!(c == l);
+        event_headers.set_len(c as usize);
+        event_headers
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn set_output(output: &str) {
+    unsafe { ffi::set_output(output.as_ptr(), output.len() as i32) }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn set_error_code(code: i16) {
+    unsafe { ffi::set_error_code(code) }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn error_log(message: &str) {
+    flowsnet_platform_sdk::write_error_log!(message);
+}
+
+/// Send a request to the integration on flows.network.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn platform_request(request: TransportRequest<'_>) -> Result<(Response, String)> {
+    crate::http::transport::send_blocking(request)
+}
+
+/// The transport of the [`Http`] clients built without one.
+///
+/// [`Http`]: crate::http::Http
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn transport() -> Arc<dyn Transport> {
+    Arc::new(TRANSPORT.clone())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn is_listening() -> bool {
+    crate::testing::with_host(|h| h.is_listening())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn flows_user() -> String {
+    crate::testing::with_host(|h| h.flows_user())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn flow_id() -> String {
+    crate::testing::with_host(|h| h.flow_id())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn event_body() -> Vec<u8> {
+    crate::testing::with_host(|h| h.event_body())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn event_headers() -> Vec<u8> {
+    crate::testing::with_host(|h| h.event_headers())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn set_output(output: &str) {
+    crate::testing::with_host(|h| h.set_output(output))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn set_error_code(code: i16) {
+    crate::testing::with_host(|h| h.set_error_code(code))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn error_log(message: &str) {
+    crate::testing::with_host(|h| h.error_log(message))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn platform_request(request: TransportRequest<'_>) -> Result<(Response, String)> {
+    crate::testing::with_host(|h| h.transport().respond(request))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn transport() -> Arc<dyn Transport> {
+    match crate::testing::installed_transport() {
+        Some(mock) => Arc::new(mock),
+        None => Arc::new(TRANSPORT.clone()),
+    }
+}
@@ -6,7 +6,7 @@ use serenity::model::prelude::{
     FollowedChannel, Gateway, GuildChannel, GuildInfo, GuildPreview, GuildPrune,
     GuildWelcomeScreen, GuildWidget, Incident, Integration, Invite, Maintenance, Member, Message,
     PartialGuild, PrivateChannel, ReactionType, RichInvite, Role, ScheduledEvent,
-    ScheduledEventUser, StageInstance, ThreadMember, ThreadsData,
+    ScheduledEventUser, StageInstance, ThreadMember, ThreadsData, Timestamp,
 };
 use serenity::model::sticker::{Sticker, StickerPack};
 use serenity::model::user::{CurrentUser, User};
@@ -23,21 +23,26 @@ use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde_json::to_vec;
 use serde_json::Value;
+use tokio::time::sleep;
 use tracing::{debug, instrument, trace};
 use url::Url;
 
 use super::multipart::Multipart;
+use super::ratelimiting::{retry_after, Ratelimiter};
 use super::request::Request;
+use super::retry::{is_transport_error, RetryPolicy, RetryReason};
 use super::routing::RouteInfo;
+use super::transport::Transport;
 use super::typing::Typing;
 use super::utils::{self, as_data, from_number};
 use super::{AttachmentType, GuildPagination, HttpError, UserPagination};
 use serenity::constants;
 use serenity::json::JsonMap;
 use serenity::model::application::command::{Command, CommandPermission};
+use serenity::model::application::interaction::InteractionResponseType;
 use serenity::model::guild::automod::Rule;
-use serenity::Error;
-use serenity::Result;
+
+use crate::{Error, Result};
 
 /// A builder for the underlying [`Http`] client that performs requests
 /// to Discord's HTTP API. If you do not need to use a proxy or do not
@@ -61,6 +66,10 @@ use serenity::Result;
 pub struct HttpBuilder {
     token: String,
     application_id: Option<u64>,
+    ratelimiter: Option<Ratelimiter>,
+    ratelimiter_disabled: bool,
+    retry_policy: RetryPolicy,
+    transport: Option<Arc<dyn Transport>>,
 }
 
 impl HttpBuilder {
@@ -70,6 +79,10 @@ impl HttpBuilder {
         Self {
             token: parse_token(token),
             application_id: None,
+            ratelimiter: None,
+            ratelimiter_disabled: false,
+            retry_policy: RetryPolicy::default(),
+            transport: None,
         }
     }
 
@@ -90,15 +103,66 @@ impl HttpBuilder {
         self
     }
 
+    /// Sets the [`Ratelimiter`] to be used. Sharing a ratelimiter between
+    /// several [`Http`] clients of the same bot lets them respect each
+    /// other's buckets.
+    #[must_use]
+    pub fn ratelimiter(mut self, ratelimiter: Ratelimiter) -> Self {
+        self.ratelimiter = Some(ratelimiter);
+
+        self
+    }
+
+    /// Sets whether or not the ratelimiter is disabled. By default if this this
+    /// not used, it is enabled. In most cases, this should be used in
+    /// conjunction with a proxy that handles ratelimiting itself.
+    #[must_use]
+    pub fn ratelimiter_disabled(mut self, ratelimiter_disabled: bool) -> Self {
+        self.ratelimiter_disabled = ratelimiter_disabled;
+
+        self
+    }
+
+    /// Sets the [`RetryPolicy`] of the requests failing transiently.
+    #[must_use]
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+
+        self
+    }
+
+    /// Sets the [`Transport`] sending the requests, a [`TokioTransport`]
+    /// shared by the clients by default. Give it a [`MockTransport`] to test
+    /// the code using the client without a network.
+    ///
+    /// In native builds, the default is the transport of the [`TestHost`]
+    /// installed on the current thread, if any.
+    ///
+    /// [`TokioTransport`]: super::TokioTransport
+    /// [`MockTransport`]: super::MockTransport
+    /// [`TestHost`]: crate::testing::TestHost
+    #[must_use]
+    pub fn transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Arc::new(transport));
+
+        self
+    }
+
     /// Use the given configuration to build the `Http` client.
     #[must_use]
     pub fn build(self) -> Http {
         let token = self.token;
         let application_id = AtomicU64::new(self.application_id.unwrap_or_default());
+        let ratelimiter = self.ratelimiter.unwrap_or_default();
+        let transport = self.transport.unwrap_or_else(crate::host::transport);
 
         Http {
             token,
             application_id,
+            ratelimiter,
+            ratelimiter_disabled: self.ratelimiter_disabled,
+            retry_policy: self.retry_policy,
+            transport,
         }
     }
 }
@@ -127,6 +191,10 @@ fn reason_into_header(reason: &str) -> Headers {
 pub struct Http {
     pub token: String,
     application_id: AtomicU64,
+    pub ratelimiter: Ratelimiter,
+    pub ratelimiter_disabled: bool,
+    retry_policy: RetryPolicy,
+    transport: Arc<dyn Transport>,
 }
 
 impl fmt::Debug for Http {
@@ -143,6 +211,10 @@ impl Http {
         Http {
             token,
             application_id: AtomicU64::new(0),
+            ratelimiter: Ratelimiter::new(),
+            ratelimiter_disabled: false,
+            retry_policy: RetryPolicy::default(),
+            transport: crate::host::transport(),
         }
     }
 
@@ -167,7 +239,7 @@ impl Http {
 
     fn try_application_id(&self) -> Result<u64> {
         self.application_id()
-            .ok_or_else(|| Error::Url(HttpError::ApplicationIdMissing.to_string()))
+            .ok_or_else(|| HttpError::ApplicationIdMissing.into())
     }
 
     pub fn set_application_id(&self, application_id: u64) {
@@ -635,6 +707,173 @@ impl Http {
         .await
     }
 
+    /// Responds to an [`Interaction`] with a message.
+    ///
+    /// `map` is the message data, e.g. `{"content": "Pong"}`.
+    ///
+    /// [`Interaction`]: crate::model::application::interaction::Interaction
+    pub async fn reply_to_interaction(
+        &self,
+        interaction_id: u64,
+        interaction_token: &str,
+        map: &Value,
+    ) -> Result<()> {
+        self.respond_to_interaction(
+            interaction_id,
+            interaction_token,
+            InteractionResponseType::ChannelMessageWithSource,
+            Some(map),
+        )
+        .await
+    }
+
+    /// Responds to an [`Interaction`] with a message, or edits the deferred
+    /// response when the integration already deferred the interaction.
+    ///
+    /// Further messages are sent with [`Self::create_followup_message`] in
+    /// both cases.
+    ///
+    /// [`Interaction`]: crate::model::application::interaction::Interaction
+    pub async fn send_interaction_message(
+        &self,
+        interaction_id: u64,
+        interaction_token: &str,
+        map: &Value,
+    ) -> Result<()> {
+        match crate::defer::is_deferred() {
+            true => self
+                .edit_original_interaction_response(interaction_token, map)
+                .await
+                .map(|_| ()),
+            false => {
+                self.reply_to_interaction(interaction_id, interaction_token, map)
+                    .await
+            }
+        }
+    }
+
+    /// Edits the message a component is attached to in response to a
+    /// [`MessageComponentInteraction`], whether the integration already
+    /// deferred the interaction or not.
+    ///
+    /// [`MessageComponentInteraction`]: crate::model::application::interaction::message_component::MessageComponentInteraction
+    pub async fn update_interaction_message(
+        &self,
+        interaction_id: u64,
+        interaction_token: &str,
+        map: &Value,
+    ) -> Result<()> {
+        match crate::defer::is_deferred() {
+            true => self
+                .edit_original_interaction_response(interaction_token, map)
+                .await
+                .map(|_| ()),
+            false => {
+                self.update_component_message(interaction_id, interaction_token, map)
+                    .await
+            }
+        }
+    }
+
+    /// Acknowledges a [`MessageComponentInteraction`] without editing the
+    /// message the component is attached to yet.
+    ///
+    /// The message can be edited later with
+    /// [`Self::edit_original_interaction_response`].
+    ///
+    /// [`MessageComponentInteraction`]: crate::model::application::interaction::message_component::MessageComponentInteraction
+    pub async fn defer_component_update(
+        &self,
+        interaction_id: u64,
+        interaction_token: &str,
+    ) -> Result<()> {
+        self.respond_to_interaction(
+            interaction_id,
+            interaction_token,
+            InteractionResponseType::DeferredUpdateMessage,
+            None,
+        )
+        .await
+    }
+
+    /// Responds to a [`MessageComponentInteraction`] by editing the message
+    /// the component is attached to.
+    ///
+    /// [`MessageComponentInteraction`]: crate::model::application::interaction::message_component::MessageComponentInteraction
+    pub async fn update_component_message(
+        &self,
+        interaction_id: u64,
+        interaction_token: &str,
+        map: &Value,
+    ) -> Result<()> {
+        self.respond_to_interaction(
+            interaction_id,
+            interaction_token,
+            InteractionResponseType::UpdateMessage,
+            Some(map),
+        )
+        .await
+    }
+
+    /// Responds to an [`AutocompleteInteraction`] with the suggested choices.
+    ///
+    /// `choices` is an array of `{"name": .., "value": ..}` objects, at most
+    /// 25 of them.
+    ///
+    /// [`AutocompleteInteraction`]: crate::model::application::interaction::autocomplete::AutocompleteInteraction
+    pub async fn create_autocomplete_response(
+        &self,
+        interaction_id: u64,
+        interaction_token: &str,
+        choices: &Value,
+    ) -> Result<()> {
+        self.respond_to_interaction(
+            interaction_id,
+            interaction_token,
+            InteractionResponseType::Autocomplete,
+            Some(&json!({ "choices": choices })),
+        )
+        .await
+    }
+
+    /// Responds to an [`Interaction`] by popping up a modal.
+    ///
+    /// `map` must contain the `custom_id`, `title` and `components` of the
+    /// modal. A [`ModalSubmitInteraction`] is received once the user submits it.
+    ///
+    /// [`Interaction`]: crate::model::application::interaction::Interaction
+    /// [`ModalSubmitInteraction`]: crate::model::application::interaction::modal::ModalSubmitInteraction
+    pub async fn create_modal_response(
+        &self,
+        interaction_id: u64,
+        interaction_token: &str,
+        map: &Value,
+    ) -> Result<()> {
+        self.respond_to_interaction(
+            interaction_id,
+            interaction_token,
+            InteractionResponseType::Modal,
+            Some(map),
+        )
+        .await
+    }
+
+    async fn respond_to_interaction(
+        &self,
+        interaction_id: u64,
+        interaction_token: &str,
+        kind: InteractionResponseType,
+        data: Option<&Value>,
+    ) -> Result<()> {
+        let mut map = json!({ "type": kind as u8 });
+        if let Some(data) = data {
+            map["data"] = data.clone();
+        }
+
+        self.create_interaction_response(interaction_id, interaction_token, &map)
+            .await
+    }
+
     /// Creates a [`RichInvite`] for the given [channel][`GuildChannel`].
     ///
     /// Refer to Discord's [docs] for field information.
@@ -2432,11 +2671,12 @@ impl Http {
         .await
     }
 
-    /// Gets all archived public threads from a channel.
+    /// Gets the archived public threads from a channel, archived before
+    /// the `before` timestamp, if any, the most recently archived first.
     pub async fn get_channel_archived_public_threads(
         &self,
         channel_id: u64,
-        before: Option<u64>,
+        before: Option<Timestamp>,
         limit: Option<u64>,
     ) -> Result<ThreadsData> {
         self.fire(Request {
@@ -2452,11 +2692,12 @@ impl Http {
         .await
     }
 
-    /// Gets all archived private threads from a channel.
+    /// Gets the archived private threads from a channel, archived before
+    /// the `before` timestamp, if any, the most recently archived first.
     pub async fn get_channel_archived_private_threads(
         &self,
         channel_id: u64,
-        before: Option<u64>,
+        before: Option<Timestamp>,
         limit: Option<u64>,
     ) -> Result<ThreadsData> {
         self.fire(Request {
@@ -2686,6 +2927,22 @@ impl Http {
         .await
     }
 
+    /// Fetches all of the global commands for your application, as raw
+    /// objects including the localizations of their names and descriptions.
+    pub(crate) async fn get_global_application_commands_with_localizations(
+        &self,
+    ) -> Result<Vec<Value>> {
+        self.fire(Request {
+            body: None,
+            multipart: None,
+            headers: None,
+            route: RouteInfo::GetGlobalApplicationCommandsWithLocalizations {
+                application_id: self.try_application_id()?,
+            },
+        })
+        .await
+    }
+
     /// Fetches a global commands for your application by its Id.
     pub async fn get_global_application_command(&self, command_id: u64) -> Result<Command> {
         self.fire(Request {
@@ -2736,6 +2993,25 @@ impl Http {
         .await
     }
 
+    /// Fetches all of the guild commands for your application for a specific
+    /// guild, as raw objects including the localizations of their names and
+    /// descriptions.
+    pub(crate) async fn get_guild_application_commands_with_localizations(
+        &self,
+        guild_id: u64,
+    ) -> Result<Vec<Value>> {
+        self.fire(Request {
+            body: None,
+            multipart: None,
+            headers: None,
+            route: RouteInfo::GetGuildApplicationCommandsWithLocalizations {
+                application_id: self.try_application_id()?,
+                guild_id,
+            },
+        })
+        .await
+    }
+
     /// Fetches a guild command by its Id.
     pub async fn get_guild_application_command(
         &self,
@@ -3249,7 +3525,13 @@ impl Http {
         .await
     }
 
-    /// Gets X messages from a channel.
+    /// Gets X messages from a channel, the `query` is the raw query string.
+    ///
+    /// See [`get_messages_with`] for a typed query and [`iter_messages`] to
+    /// walk the history of the channel.
+    ///
+    /// [`get_messages_with`]: Self::get_messages_with
+    /// [`iter_messages`]: Self::iter_messages
     pub async fn get_messages(&self, channel_id: u64, query: &str) -> Result<Vec<Message>> {
         self.fire(Request {
             body: None,
@@ -3500,10 +3782,8 @@ impl Http {
     /// # }
     /// ```
     pub async fn get_webhook_from_url(&self, url: &str) -> Result<Webhook> {
-        let url = Url::parse(url).map_err(HttpError::Url).unwrap();
-        let (webhook_id, token) = utils::parse_webhook(&url)
-            .ok_or(HttpError::InvalidWebhook)
-            .unwrap();
+        let url = Url::parse(url).map_err(HttpError::Url)?;
+        let (webhook_id, token) = utils::parse_webhook(&url).ok_or(HttpError::InvalidWebhook)?;
         self.fire(Request {
             body: None,
             multipart: None,
@@ -3876,12 +4156,54 @@ impl Http {
     /// ```
     #[instrument]
     pub async fn request(&self, mut req: Request<'_>) -> Result<(Response, String)> {
-        let (res, text) = req.build(&self.token).await?;
+        let (method, _, url) = req.route_ref().deconstruct();
+        let url = url.into_owned();
+        let mut retry = 0;
+
+        loop {
+            let result = if self.ratelimiter_disabled {
+                req.build(&self.token, self.transport.as_ref()).await
+            } else {
+                self.ratelimiter
+                    .perform(&mut req, &self.token, self.transport.as_ref())
+                    .await
+            };
+
+            retry += 1;
+            let delay = match &result {
+                Ok((res, _)) if res.status_code().is_success() => None,
+                Ok((res, text)) if res.status_code() == StatusCode::from(429) => {
+                    let retry_after = retry_after(res, text).ok().flatten();
+                    let reason = RetryReason::Ratelimited;
+                    self.retry_policy
+                        .delay(method, &url, retry, reason, retry_after)
+                }
+                Ok((res, _)) if res.status_code().is_server_err() => {
+                    let reason = RetryReason::ServerError(u16::from(res.status_code()));
+                    self.retry_policy.delay(method, &url, retry, reason, None)
+                }
+                Err(e) if is_transport_error(e) => {
+                    let reason = RetryReason::Transport(e);
+                    self.retry_policy.delay(method, &url, retry, reason, None)
+                }
+                _ => None,
+            };
 
-        if res.status_code().is_success() {
-            Ok((res, text))
-        } else {
-            Err(Error::Url(res.reason().to_string()))
+            match delay {
+                Some(delay) => {
+                    debug!("Retrying {} in {}ms", url, delay.as_millis());
+                    sleep(delay).await;
+                }
+                None => {
+                    let (res, text) = result?;
+
+                    return if res.status_code().is_success() {
+                        Ok((res, text))
+                    } else {
+                        Err(HttpError::from_response(&res, &url, &text).into())
+                    };
+                }
+            }
         }
     }
 
@@ -3891,7 +4213,8 @@ impl Http {
     /// This is a function that performs a light amount of work and returns an
     /// empty tuple, so it's called "self.wind" to denote that it's lightweight.
     pub(super) async fn wind(&self, expected: u16, req: Request<'_>) -> Result<()> {
-        let (response, _) = self.request(req).await?;
+        let url = req.route_ref().deconstruct().2.into_owned();
+        let (response, text) = self.request(req).await?;
 
         if response.status_code().is(|code| code == expected) {
             return Ok(());
@@ -3900,7 +4223,7 @@ impl Http {
         debug!("Expected {}, got {}", expected, response.status_code());
         trace!("Unsuccessful response: {:?}", response);
 
-        Err(Error::Url(response.reason().to_string()))
+        Err(HttpError::from_response(&response, &url, &text).into())
     }
 }
 
@@ -34,26 +34,50 @@ pub struct DiscordJsonSingleError {
 #[derive(Debug, Clone, PartialEq)]
 pub struct ErrorResponse {
     pub status_code: StatusCode,
-    pub url: Url,
+    /// The Discord endpoint the request was proxied to, `None` for the
+    /// requests to flows.network itself.
+    pub url: Option<Url>,
     pub error: DiscordJsonError,
 }
 
 impl ErrorResponse {
-    // We need a freestanding from-function since we cannot implement an async
-    // From-trait.
-    pub async fn from_response(r: Response) -> Self {
+    /// Builds an [`ErrorResponse`] from a failed response and its body.
+    ///
+    /// If the body is not a JSON error from Discord, the error code is set to
+    /// `-1` and the message explains why decoding failed.
+    pub fn from_response(r: &Response, url: &str, text: &str) -> Self {
         ErrorResponse {
             status_code: r.status_code(),
-            url: "https://nourl.icu/".try_into().unwrap(),
-            error:DiscordJsonError {
+            url: discord_url(url),
+            error: serde_json::from_str(text).unwrap_or_else(|e| DiscordJsonError {
                 code: -1,
-                message: format!("[Serenity] Could not decode json when receiving error response from discord:, {}", r.reason()),
+                message: format!(
+                    "[Serenity] Could not decode json when receiving error response from discord:, {} ({})",
+                    r.reason(),
+                    e
+                ),
                 errors: vec![],
-            },
+            }),
         }
     }
 }
 
+/// The proxied routes of flows.network and the Discord endpoints they are
+/// forwarded to.
+const PROXIED: [(&str, &str); 2] = [
+    ("/proxy/api", "https://discord.com/api/v10"),
+    ("/proxy/status", "https://status.discord.com/api/v2"),
+];
+
+/// The Discord endpoint behind the proxied `url`.
+fn discord_url(url: &str) -> Option<Url> {
+    let path = url.strip_prefix(crate::API_PREFIX)?;
+    PROXIED.iter().find_map(|(proxy, discord)| {
+        let route = path.strip_prefix(proxy).filter(|r| r.starts_with('/'))?;
+        Url::parse(&format!("{}{}", discord, route)).ok()
+    })
+}
+
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum Error {
@@ -80,10 +104,8 @@ pub enum Error {
 }
 
 impl Error {
-    // We need a freestanding from-function since we cannot implement an async
-    // From-trait.
-    pub async fn from_response(r: Response) -> Self {
-        ErrorResponse::from_response(r).await.into()
+    pub fn from_response(r: &Response, url: &str, text: &str) -> Self {
+        ErrorResponse::from_response(r, url, text).into()
     }
 
     /// Returns true when the error is caused by an unsuccessful request
@@ -106,6 +128,15 @@ impl Error {
             _ => None,
         }
     }
+
+    /// Returns Discord's JSON error code if the error is an unsuccessful request
+    #[must_use]
+    pub fn discord_error_code(&self) -> Option<isize> {
+        match self {
+            Self::UnsuccessfulRequest(res) => Some(res.error.code),
+            _ => None,
+        }
+    }
 }
 
 impl From<ErrorResponse> for Error {
@@ -171,3 +202,28 @@ impl StdError for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::utils::{api, status};
+    use std::fmt::Write as _;
+
+    #[test]
+    fn records_the_discord_endpoint() {
+        let url = discord_url(&api!("/channels/{}/messages", 1));
+        assert_eq!(
+            url.unwrap().as_str(),
+            "https://discord.com/api/v10/channels/1/messages"
+        );
+
+        let url = discord_url(status!("/incidents/unresolved.json"));
+        assert_eq!(
+            url.unwrap().as_str(),
+            "https://status.discord.com/api/v2/incidents/unresolved.json"
+        );
+
+        let listen = format!("{}/user/flow/listen", crate::API_PREFIX);
+        assert_eq!(discord_url(&listen), None);
+    }
+}
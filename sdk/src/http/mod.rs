@@ -14,7 +14,8 @@
 //! The former require a [`Client`] to have logged in, while the latter may be
 //! made regardless of any other usage of the library.
 //!
-//! If a request spuriously fails, it will be retried once.
+//! If a request spuriously fails, it is retried according to the
+//! [`RetryPolicy`] of the client, by default once when it is safe to.
 //!
 //! Note that you may want to perform requests through a [model]s'
 //! instance methods where possible, as they each offer different
@@ -26,8 +27,13 @@
 pub mod client;
 pub mod error;
 pub mod multipart;
+pub mod pagination;
+pub mod ratelimiting;
 pub mod request;
+pub mod retry;
 pub mod routing;
+pub mod tokio_transport;
+pub mod transport;
 pub mod typing;
 mod utils;
 
@@ -35,6 +41,10 @@ use http_req::request::Method;
 
 pub use self::client::*;
 pub use self::error::Error as HttpError;
+pub use self::pagination::*;
+pub use self::retry::{RetryAttempt, RetryPolicy, RetryReason};
+pub use self::tokio_transport::TokioTransport;
+pub use self::transport::*;
 pub use self::typing::*;
 use crate::model::*;
 
@@ -98,6 +108,7 @@ pub enum GuildPagination {
 /// Representation of the method of a query to send for the [`get_scheduled_event_users`] function.
 ///
 /// [`get_scheduled_event_users`]: Http::get_scheduled_event_users
+#[derive(Clone, Copy, Debug)]
 #[non_exhaustive]
 pub enum UserPagination {
     /// The Id to get the users after.
@@ -105,3 +116,15 @@ pub enum UserPagination {
     /// The Id to get the users before.
     Before(UserId),
 }
+
+/// Representation of the method of a [`MessageQuery`].
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum MessagePagination {
+    /// The Id to get the messages after.
+    After(MessageId),
+    /// The Id to get the messages around.
+    Around(MessageId),
+    /// The Id to get the messages before.
+    Before(MessageId),
+}
@@ -1,12 +1,11 @@
 use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-// use reqwest::multipart::{Form, Part};
-// use reqwest::Client;
+use serde_json::Value;
 
 use super::AttachmentType;
-// use http_req::request::RequestBuilder;
-use serde_json::Value;
-// use serenity::Result;
+use crate::{Error, Result};
 
 /// Holder for multipart body. Contains files, multipart fields, and
 /// payload_json for creating requests with attachments.
@@ -22,57 +21,250 @@ pub struct Multipart<'a> {
     pub payload_json: Option<Value>,
 }
 
-// impl<'a> Multipart<'a> {
-//     pub(crate) async fn build_form(&mut self, builder: &RequestBuilder<'a>) -> Result<()> {
-//             let mut multipart = Form::new();
-//
-//             for (file_num, file) in self.files.iter_mut().enumerate() {
-//                 // For endpoints that require a single file (e.g. create sticker),
-//                 // it will error if the part name is not `file`.
-//                 // https://github.com/discord/discord-api-docs/issues/2064#issuecomment-691650970
-//                 let part_name =
-//                     if file_num == 0 { "file".to_string() } else { format!("file{}", file_num) };
-//
-//                 let data = file.data(client).await?;
-//                 let filename = file.filename()?;
-//
-//                 // Modify current AttachmentType to Bytes variant to prevent the
-//                 // need for another disk read or network request when retrying
-//                 if let AttachmentType::Path(_) | AttachmentType::Image(_) = file {
-//                     *file = AttachmentType::Bytes {
-//                         data: data.clone().into(),
-//                         filename: filename.clone().unwrap_or_default(),
-//                     };
-//                 }
-//
-//                 let mut part = Part::bytes(data);
-//                 if let Some(filename) = filename {
-//                     part = guess_mime_str(part, &filename)?;
-//                     part = part.file_name(filename);
-//                 }
-//                 multipart = multipart.part(part_name, part);
-//             }
-//
-//             for (name, value) in &self.fields {
-//                 multipart = multipart.text(name.clone(), value.clone());
-//             }
-//
-//             if let Some(ref payload_json) = self.payload_json {
-//                 multipart = multipart.text("payload_json", json::to_string(payload_json)?);
-//             }
-//
-//             Ok(multipart)
-//         unimplemented!()
-//     }
-// }
-
-// fn guess_mime_str(part: Part, filename: &str) -> Result<Part> {
-//     // This is required for certain endpoints like create sticker, otherwise
-//     // the Discord API will respond with a 500 Internal Server Error.
-//     // The mime type chosen is the same as what reqwest does internally when
-//     // using Part::file(), but it is not done for any of the other methods we
-//     // use.
-//     // https://datatracker.ietf.org/doc/html/rfc7578#section-4.4
-//     let mime_type = mime_guess::from_path(filename).first_or_octet_stream();
-//     part.mime_str(mime_type.essence_str()).map_err(Into::into)
-// }
+/// A `multipart/form-data` body ready to be sent, along with the boundary
+/// it was encoded with.
+#[derive(Clone, Debug)]
+pub struct Form {
+    pub boundary: String,
+    pub body: Vec<u8>,
+}
+
+impl Form {
+    /// The value of the `Content-Type` header for this form.
+    #[must_use]
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+}
+
+impl<'a> Multipart<'a> {
+    pub(crate) fn build_form(&mut self) -> Result<Form> {
+        let mut parts = Vec::new();
+
+        for (file_num, file) in self.files.iter_mut().enumerate() {
+            // For endpoints that require a single file (e.g. create sticker),
+            // it will error if the part name is not `file`.
+            // https://github.com/discord/discord-api-docs/issues/2064#issuecomment-691650970
+            let part_name = if file_num == 0 {
+                "file".to_string()
+            } else {
+                format!("file{}", file_num)
+            };
+
+            let data = attachment_data(file)?;
+            let filename = attachment_filename(file)?;
+
+            // Modify current AttachmentType to Bytes variant to prevent the
+            // need for another disk read when retrying
+            if let AttachmentType::Path(_) = file {
+                *file = AttachmentType::Bytes {
+                    data: data.clone().into(),
+                    filename: filename.clone().unwrap_or_default(),
+                };
+            }
+
+            let mut head = format!(
+                "Content-Disposition: form-data; name=\"{}\"",
+                escape_quoted(&part_name)
+            );
+            if let Some(ref filename) = filename {
+                _ = write!(head, "; filename=\"{}\"", escape_quoted(filename));
+            }
+            head.push_str("\r\n");
+
+            // This is required for certain endpoints like create sticker, otherwise
+            // the Discord API will respond with a 500 Internal Server Error.
+            // https://datatracker.ietf.org/doc/html/rfc7578#section-4.4
+            let mime_type = match filename {
+                Some(ref filename) => mime_guess::from_path(filename).first_or_octet_stream(),
+                None => mime_guess::mime::APPLICATION_OCTET_STREAM,
+            };
+            _ = write!(head, "Content-Type: {}\r\n", mime_type.essence_str());
+
+            parts.push(Part { head, data });
+        }
+
+        for (name, value) in &self.fields {
+            parts.push(Part::text(name, value.as_bytes().to_vec()));
+        }
+
+        if let Some(ref payload_json) = self.payload_json {
+            parts.push(Part::text(
+                "payload_json",
+                serde_json::to_vec(payload_json)?,
+            ));
+        }
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let boundary = boundary(nanos, &parts);
+
+        let mut body = Vec::new();
+        for part in parts {
+            body.extend_from_slice(format!("--{}\r\n{}\r\n", boundary, part.head).as_bytes());
+            body.extend_from_slice(&part.data);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+        Ok(Form { boundary, body })
+    }
+}
+
+/// A part of the form, its headers each ending with a CRLF.
+struct Part {
+    head: String,
+    data: Vec<u8>,
+}
+
+impl Part {
+    fn text(name: &str, data: Vec<u8>) -> Self {
+        Self {
+            head: format!(
+                "Content-Disposition: form-data; name=\"{}\"\r\n",
+                escape_quoted(name)
+            ),
+            data,
+        }
+    }
+}
+
+fn escape_quoted(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// A boundary derived from `seed` which occurs in none of the parts, so
+/// that no part is cut short.
+fn boundary(seed: u128, parts: &[Part]) -> String {
+    (0..)
+        .map(|i| format!("----DiscordFlowsFormBoundary{:032x}", seed.wrapping_add(i)))
+        .find(|boundary| {
+            let boundary = boundary.as_bytes();
+            parts.iter().all(|part| {
+                !contains(part.head.as_bytes(), boundary) && !contains(&part.data, boundary)
+            })
+        })
+        .expect("the boundaries are endless")
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+fn attachment_data(file: &AttachmentType<'_>) -> Result<Vec<u8>> {
+    match file {
+        AttachmentType::Bytes { data, .. } => Ok(data.clone().into_owned()),
+        AttachmentType::File { .. } => Err(Error::Other(
+            "AttachmentType::File is not supported, use AttachmentType::Path or Bytes instead",
+        )),
+        AttachmentType::Path(path) => std::fs::read(path).map_err(From::from),
+        // Downloading the image would block the runtime, the caller fetches
+        // it with its own client instead.
+        AttachmentType::Image(_) => Err(Error::Other(
+            "AttachmentType::Image is not supported, download the image and use AttachmentType::Bytes instead",
+        )),
+        _ => Err(Error::Other("Unsupported attachment type")),
+    }
+}
+
+fn attachment_filename(file: &AttachmentType<'_>) -> Result<Option<String>> {
+    match file {
+        AttachmentType::Bytes { filename, .. } | AttachmentType::File { filename, .. } => {
+            Ok(Some(filename.to_string()))
+        }
+        AttachmentType::Path(path) => Ok(path
+            .file_name()
+            .map(|filename| filename.to_string_lossy().to_string())),
+        AttachmentType::Image(url) => match url.path_segments().and_then(Iterator::last) {
+            Some(filename) => Ok(Some(filename.to_string())),
+            None => Err(Error::Url(url.to_string())),
+        },
+        _ => Err(Error::Other("Unsupported attachment type")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn bytes(data: &'static [u8], filename: &str) -> AttachmentType<'static> {
+        AttachmentType::Bytes {
+            data: data.into(),
+            filename: filename.to_string(),
+        }
+    }
+
+    #[test]
+    fn builds_the_parts_with_their_content_types() {
+        let mut multipart = Multipart {
+            files: vec![bytes(b"png", "cat.png"), bytes(b"raw", "data")],
+            fields: vec![("name".into(), "sticker".into())],
+            payload_json: Some(json!({ "content": "hi" })),
+        };
+
+        let form = multipart.build_form().unwrap();
+        let body = String::from_utf8(form.body.clone()).unwrap();
+        let b = &form.boundary;
+
+        let expected = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"cat.png\"\r\n\
+             Content-Type: image/png\r\n\r\npng\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"file1\"; filename=\"data\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\nraw\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\nsticker\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"payload_json\"\r\n\r\n\
+             {{\"content\":\"hi\"}}\r\n\
+             --{b}--\r\n"
+        );
+        assert_eq!(body, expected);
+        assert_eq!(
+            form.content_type(),
+            format!("multipart/form-data; boundary={}", b)
+        );
+    }
+
+    #[test]
+    fn escapes_the_filenames() {
+        let mut multipart = Multipart {
+            files: vec![bytes(b"", "a \"quoted\"\r\nname\\.txt")],
+            fields: vec![],
+            payload_json: None,
+        };
+
+        let form = multipart.build_form().unwrap();
+        let body = String::from_utf8(form.body).unwrap();
+
+        assert!(body.contains("filename=\"a \\\"quoted\\\"%0D%0Aname\\\\.txt\"\r\n"));
+        assert!(body.contains("Content-Type: text/plain\r\n"));
+    }
+
+    #[test]
+    fn refuses_the_images_to_download() {
+        let mut multipart = Multipart {
+            files: vec![AttachmentType::Image(
+                "https://example.com/cat.png".parse().unwrap(),
+            )],
+            fields: vec![],
+            payload_json: None,
+        };
+
+        assert!(multipart.build_form().is_err());
+    }
+
+    #[test]
+    fn picks_a_boundary_absent_from_the_parts() {
+        let taken = format!("{}{}", boundary(7, &[]), boundary(8, &[]));
+        let parts = [Part::text("file", taken.into_bytes())];
+
+        assert_eq!(boundary(7, &parts), boundary(9, &[]));
+    }
+}
@@ -0,0 +1,853 @@
+//! Typed queries of the list endpoints and [`Paginator`]s walking them page
+//! by page.
+//!
+//! A paginator keeps the cursor of the endpoint up to date and yields the
+//! items one at a time, until the endpoint is exhausted or the cap set with
+//! [`Paginator::max_items`] is reached.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! # async fn run(http: &discord_flows::http::Http) -> discord_flows::Result<()> {
+//! use discord_flows::http::MessageQuery;
+//!
+//! let mut messages = http.iter_messages(123456, MessageQuery::new());
+//! while let Some(message) = messages.next().await {
+//!     let message = message?;
+//!     println!("{}: {}", message.author.name, message.content);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use async_trait::async_trait;
+use serenity::constants;
+use serenity::model::guild::audit_log::AuditLogEntry;
+use serenity::model::prelude::*;
+use serenity::model::Timestamp;
+
+use super::{Http, MessagePagination, UserPagination};
+use crate::{Error, Result};
+
+/// The most messages Discord returns in a page.
+pub const MESSAGE_PAGE_LIMIT: u8 = 100;
+/// The most users Discord returns in a page of reaction or scheduled event
+/// users.
+pub const USER_PAGE_LIMIT: u8 = 100;
+/// The most entries Discord returns in a page of audit logs.
+pub const AUDIT_LOG_PAGE_LIMIT: u8 = 100;
+
+/// The query of [`Http::get_messages_with`] and [`Http::iter_messages`].
+///
+/// Without a target, the most recent messages of the channel are returned.
+#[derive(Clone, Copy, Debug, Default)]
+#[must_use]
+pub struct MessageQuery {
+    target: Option<MessagePagination>,
+    limit: Option<u8>,
+}
+
+impl MessageQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the messages sent before this one, the most recent first.
+    pub fn before(mut self, message_id: impl Into<MessageId>) -> Self {
+        self.target = Some(MessagePagination::Before(message_id.into()));
+
+        self
+    }
+
+    /// Get the messages sent after this one.
+    pub fn after(mut self, message_id: impl Into<MessageId>) -> Self {
+        self.target = Some(MessagePagination::After(message_id.into()));
+
+        self
+    }
+
+    /// Get the messages sent around this one, a single page.
+    pub fn around(mut self, message_id: impl Into<MessageId>) -> Self {
+        self.target = Some(MessagePagination::Around(message_id.into()));
+
+        self
+    }
+
+    /// Set the number of messages per page, 1 to 100, 50 by default.
+    pub fn limit(mut self, limit: u8) -> Self {
+        self.limit = Some(limit);
+
+        self
+    }
+
+    fn check(&self) -> Result<()> {
+        check_limit(self.limit, MESSAGE_PAGE_LIMIT)
+    }
+}
+
+/// Formats the query string of the query, with its leading `?`.
+impl fmt::Display for MessageQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("?")?;
+        if let Some(limit) = self.limit {
+            write!(f, "limit={}&", limit)?;
+        }
+        match self.target {
+            Some(MessagePagination::After(id)) => write!(f, "after={}", id),
+            Some(MessagePagination::Around(id)) => write!(f, "around={}", id),
+            Some(MessagePagination::Before(id)) => write!(f, "before={}", id),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The query of [`Http::iter_guild_members`] and [`Http::iter_reaction_users`],
+/// the users are listed by increasing Id.
+#[derive(Clone, Copy, Debug, Default)]
+#[must_use]
+pub struct UserQuery {
+    after: Option<UserId>,
+    limit: Option<u64>,
+}
+
+impl UserQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the users with an Id greater than this one.
+    pub fn after(mut self, user_id: impl Into<UserId>) -> Self {
+        self.after = Some(user_id.into());
+
+        self
+    }
+
+    /// Set the number of users per page, up to 1000 members or 100 users of
+    /// a reaction.
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+
+        self
+    }
+}
+
+/// The query of [`Http::iter_audit_logs`], the most recent entries first.
+#[derive(Clone, Copy, Debug, Default)]
+#[must_use]
+pub struct AuditLogQuery {
+    action_type: Option<u8>,
+    user_id: Option<UserId>,
+    before: Option<AuditLogEntryId>,
+    limit: Option<u8>,
+}
+
+impl AuditLogQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only get the entries of this [`Action`], by its number.
+    ///
+    /// [`Action`]: crate::model::guild::audit_log::Action
+    pub fn action_type(mut self, action_type: u8) -> Self {
+        self.action_type = Some(action_type);
+
+        self
+    }
+
+    /// Only get the entries of the actions of this user.
+    pub fn user_id(mut self, user_id: impl Into<UserId>) -> Self {
+        self.user_id = Some(user_id.into());
+
+        self
+    }
+
+    /// Get the entries older than this one.
+    pub fn before(mut self, entry_id: impl Into<AuditLogEntryId>) -> Self {
+        self.before = Some(entry_id.into());
+
+        self
+    }
+
+    /// Set the number of entries per page, 1 to 100, 50 by default.
+    pub fn limit(mut self, limit: u8) -> Self {
+        self.limit = Some(limit);
+
+        self
+    }
+}
+
+/// The query of [`Http::iter_channel_archived_public_threads`], the most
+/// recently archived threads first.
+#[derive(Clone, Copy, Debug, Default)]
+#[must_use]
+pub struct ArchivedThreadQuery {
+    before: Option<Timestamp>,
+    limit: Option<u64>,
+}
+
+impl ArchivedThreadQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the threads archived before this time.
+    pub fn before(mut self, timestamp: Timestamp) -> Self {
+        self.before = Some(timestamp);
+
+        self
+    }
+
+    /// Set the number of threads per page, 2 or more, 50 by default.
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+
+        self
+    }
+}
+
+/// The query of [`Http::iter_scheduled_event_users`].
+#[derive(Clone, Copy, Debug, Default)]
+#[must_use]
+pub struct ScheduledEventUserQuery {
+    target: Option<UserPagination>,
+    limit: Option<u64>,
+    with_member: Option<bool>,
+}
+
+impl ScheduledEventUserQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the users with an Id lower than this one, by decreasing Id.
+    pub fn before(mut self, user_id: impl Into<UserId>) -> Self {
+        self.target = Some(UserPagination::Before(user_id.into()));
+
+        self
+    }
+
+    /// Get the users with an Id greater than this one, by increasing Id.
+    pub fn after(mut self, user_id: impl Into<UserId>) -> Self {
+        self.target = Some(UserPagination::After(user_id.into()));
+
+        self
+    }
+
+    /// Set the number of users per page, 1 to 100.
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+
+        self
+    }
+
+    /// Get the guild member of each user.
+    pub fn with_member(mut self, with_member: bool) -> Self {
+        self.with_member = Some(with_member);
+
+        self
+    }
+}
+
+/// A list endpoint fetched page by page, the pages of a [`Paginator`].
+#[async_trait(?Send)]
+pub trait Pages {
+    type Item;
+
+    /// Fetch the next page and move the cursor after it, an empty page means
+    /// the endpoint is exhausted.
+    async fn next_page(&mut self, http: &Http) -> Result<Vec<Self::Item>>;
+}
+
+/// An async iterator over the items of a list endpoint, fetching the next
+/// page only once the items of the previous one are consumed.
+///
+/// The iteration stops at the first error, which is yielded.
+#[must_use]
+pub struct Paginator<'a, P: Pages> {
+    http: &'a Http,
+    pages: P,
+    buffer: VecDeque<P::Item>,
+    remaining: Option<usize>,
+    done: bool,
+}
+
+impl<'a, P: Pages> Paginator<'a, P> {
+    fn new(http: &'a Http, pages: P) -> Self {
+        Self {
+            http,
+            pages,
+            buffer: VecDeque::new(),
+            remaining: None,
+            done: false,
+        }
+    }
+
+    /// Stop after yielding `max` items, whatever remains to be fetched.
+    pub fn max_items(mut self, max: usize) -> Self {
+        self.remaining = Some(max);
+
+        self
+    }
+
+    /// Yield the next item, `None` once the endpoint or the cap is exhausted.
+    pub async fn next(&mut self) -> Option<Result<P::Item>> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+
+        if self.buffer.is_empty() && !self.done {
+            match self.pages.next_page(self.http).await {
+                Ok(page) if page.is_empty() => self.done = true,
+                Ok(page) => self.buffer.extend(page),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        let item = self.buffer.pop_front()?;
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining -= 1;
+        }
+
+        Some(Ok(item))
+    }
+
+    /// Collect the remaining items.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of the requests, the items fetched before it
+    /// are dropped.
+    pub async fn try_collect(mut self) -> Result<Vec<P::Item>> {
+        let mut items = Vec::new();
+        while let Some(item) = self.next().await {
+            items.push(item?);
+        }
+
+        Ok(items)
+    }
+}
+
+/// The pages of [`Http::iter_messages`].
+pub struct MessagePages {
+    channel_id: u64,
+    query: MessageQuery,
+    done: bool,
+}
+
+#[async_trait(?Send)]
+impl Pages for MessagePages {
+    type Item = Message;
+
+    async fn next_page(&mut self, http: &Http) -> Result<Vec<Message>> {
+        if self.done {
+            return Ok(vec![]);
+        }
+
+        let mut page = http.get_messages_with(self.channel_id, &self.query).await?;
+        self.done = page.len() < self.query.limit.unwrap_or(50) as usize;
+
+        match self.query.target {
+            // Discord returns the most recent messages first
+            Some(MessagePagination::After(_)) => {
+                page.reverse();
+                if let Some(last) = page.last() {
+                    self.query.target = Some(MessagePagination::After(last.id));
+                }
+            }
+            Some(MessagePagination::Around(_)) => self.done = true,
+            Some(MessagePagination::Before(_)) | None => {
+                if let Some(last) = page.last() {
+                    self.query.target = Some(MessagePagination::Before(last.id));
+                }
+            }
+        }
+
+        Ok(page)
+    }
+}
+
+/// The pages of [`Http::iter_guild_members`].
+pub struct MemberPages {
+    guild_id: u64,
+    query: UserQuery,
+    done: bool,
+}
+
+#[async_trait(?Send)]
+impl Pages for MemberPages {
+    type Item = Member;
+
+    async fn next_page(&mut self, http: &Http) -> Result<Vec<Member>> {
+        if self.done {
+            return Ok(vec![]);
+        }
+
+        let page = http
+            .get_guild_members(
+                self.guild_id,
+                self.query.limit,
+                self.query.after.map(|id| id.0),
+            )
+            .await?;
+        self.done = page.len() < self.query.limit.unwrap_or(constants::MEMBER_FETCH_LIMIT) as usize;
+        if let Some(last) = page.last() {
+            self.query.after = Some(last.user.id);
+        }
+
+        Ok(page)
+    }
+}
+
+/// The pages of [`Http::iter_reaction_users`].
+pub struct ReactionUserPages {
+    channel_id: u64,
+    message_id: u64,
+    reaction_type: ReactionType,
+    query: UserQuery,
+    done: bool,
+}
+
+#[async_trait(?Send)]
+impl Pages for ReactionUserPages {
+    type Item = User;
+
+    async fn next_page(&mut self, http: &Http) -> Result<Vec<User>> {
+        if self.done {
+            return Ok(vec![]);
+        }
+
+        let limit = self.query.limit.unwrap_or(USER_PAGE_LIMIT as u64);
+        if !(1..=USER_PAGE_LIMIT as u64).contains(&limit) {
+            return Err(Error::NotInRange("limit", limit, 1, USER_PAGE_LIMIT as u64));
+        }
+
+        let page = http
+            .get_reaction_users(
+                self.channel_id,
+                self.message_id,
+                &self.reaction_type,
+                limit as u8,
+                self.query.after.map(|id| id.0),
+            )
+            .await?;
+        self.done = page.len() < limit as usize;
+        if let Some(last) = page.last() {
+            self.query.after = Some(last.id);
+        }
+
+        Ok(page)
+    }
+}
+
+/// The pages of [`Http::iter_audit_logs`].
+pub struct AuditLogPages {
+    guild_id: u64,
+    query: AuditLogQuery,
+    done: bool,
+}
+
+#[async_trait(?Send)]
+impl Pages for AuditLogPages {
+    type Item = AuditLogEntry;
+
+    async fn next_page(&mut self, http: &Http) -> Result<Vec<AuditLogEntry>> {
+        if self.done {
+            return Ok(vec![]);
+        }
+        check_limit(self.query.limit, AUDIT_LOG_PAGE_LIMIT)?;
+
+        let page = http
+            .get_audit_logs(
+                self.guild_id,
+                self.query.action_type,
+                self.query.user_id.map(|id| id.0),
+                self.query.before.map(|id| id.0),
+                self.query.limit,
+            )
+            .await?
+            .entries;
+        self.done = page.len() < self.query.limit.unwrap_or(50) as usize;
+        if let Some(last) = page.last() {
+            self.query.before = Some(last.id);
+        }
+
+        Ok(page)
+    }
+}
+
+/// The pages of [`Http::iter_channel_archived_public_threads`].
+pub struct ArchivedThreadPages {
+    channel_id: u64,
+    query: ArchivedThreadQuery,
+    done: bool,
+}
+
+#[async_trait(?Send)]
+impl Pages for ArchivedThreadPages {
+    type Item = GuildChannel;
+
+    async fn next_page(&mut self, http: &Http) -> Result<Vec<GuildChannel>> {
+        if self.done {
+            return Ok(vec![]);
+        }
+
+        let data = http
+            .get_channel_archived_public_threads(
+                self.channel_id,
+                self.query.before,
+                self.query.limit,
+            )
+            .await?;
+        let before = data
+            .threads
+            .last()
+            .and_then(|t| t.thread_metadata.as_ref())
+            .and_then(|m| m.archive_timestamp);
+        self.done = !data.has_more || before.is_none();
+        self.query.before = before;
+
+        Ok(data.threads)
+    }
+}
+
+/// The pages of [`Http::iter_scheduled_event_users`].
+pub struct ScheduledEventUserPages {
+    guild_id: u64,
+    event_id: u64,
+    query: ScheduledEventUserQuery,
+    done: bool,
+}
+
+#[async_trait(?Send)]
+impl Pages for ScheduledEventUserPages {
+    type Item = ScheduledEventUser;
+
+    async fn next_page(&mut self, http: &Http) -> Result<Vec<ScheduledEventUser>> {
+        if self.done {
+            return Ok(vec![]);
+        }
+
+        let limit = self.query.limit.unwrap_or(USER_PAGE_LIMIT as u64);
+        let mut page = http
+            .get_scheduled_event_users(
+                self.guild_id,
+                self.event_id,
+                Some(limit),
+                self.query.target,
+                self.query.with_member,
+            )
+            .await?;
+        self.done = page.len() < limit as usize;
+
+        // Discord returns the users by increasing Id either way
+        match self.query.target {
+            Some(UserPagination::Before(_)) => {
+                page.reverse();
+                if let Some(last) = page.last() {
+                    self.query.target = Some(UserPagination::Before(last.user.id));
+                }
+            }
+            _ => {
+                if let Some(last) = page.last() {
+                    self.query.target = Some(UserPagination::After(last.user.id));
+                }
+            }
+        }
+
+        Ok(page)
+    }
+}
+
+impl Http {
+    /// Gets the messages of a channel matching the `query`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NotInRange`] when the limit of the query is not
+    /// between 1 and 100.
+    pub async fn get_messages_with(
+        &self,
+        channel_id: u64,
+        query: &MessageQuery,
+    ) -> Result<Vec<Message>> {
+        query.check()?;
+
+        self.get_messages(channel_id, &query.to_string()).await
+    }
+
+    /// Iterate over the messages of a channel, from the target of the
+    /// `query` towards the oldest message, or towards the most recent one
+    /// when walking [`after`] a message.
+    ///
+    /// [`after`]: MessageQuery::after
+    pub fn iter_messages(
+        &self,
+        channel_id: u64,
+        query: MessageQuery,
+    ) -> Paginator<'_, MessagePages> {
+        let query = query.limit(query.limit.unwrap_or(MESSAGE_PAGE_LIMIT));
+
+        Paginator::new(
+            self,
+            MessagePages {
+                channel_id,
+                query,
+                done: false,
+            },
+        )
+    }
+
+    /// Iterate over the members of a guild, by increasing user Id.
+    pub fn iter_guild_members(
+        &self,
+        guild_id: u64,
+        query: UserQuery,
+    ) -> Paginator<'_, MemberPages> {
+        let query = query.limit(query.limit.unwrap_or(constants::MEMBER_FETCH_LIMIT));
+
+        Paginator::new(
+            self,
+            MemberPages {
+                guild_id,
+                query,
+                done: false,
+            },
+        )
+    }
+
+    /// Iterate over the users who reacted to a message with the
+    /// `reaction_type`, by increasing Id.
+    pub fn iter_reaction_users(
+        &self,
+        channel_id: u64,
+        message_id: u64,
+        reaction_type: ReactionType,
+        query: UserQuery,
+    ) -> Paginator<'_, ReactionUserPages> {
+        let query = query.limit(query.limit.unwrap_or(USER_PAGE_LIMIT as u64));
+
+        Paginator::new(
+            self,
+            ReactionUserPages {
+                channel_id,
+                message_id,
+                reaction_type,
+                query,
+                done: false,
+            },
+        )
+    }
+
+    /// Iterate over the entries of the audit logs of a guild, the most recent
+    /// first.
+    ///
+    /// Use [`get_audit_logs`] to get the users and webhooks referenced by the
+    /// entries.
+    ///
+    /// [`get_audit_logs`]: Self::get_audit_logs
+    pub fn iter_audit_logs(
+        &self,
+        guild_id: u64,
+        query: AuditLogQuery,
+    ) -> Paginator<'_, AuditLogPages> {
+        let query = query.limit(query.limit.unwrap_or(AUDIT_LOG_PAGE_LIMIT));
+
+        Paginator::new(
+            self,
+            AuditLogPages {
+                guild_id,
+                query,
+                done: false,
+            },
+        )
+    }
+
+    /// Iterate over the archived public threads of a channel, the most
+    /// recently archived first.
+    pub fn iter_channel_archived_public_threads(
+        &self,
+        channel_id: u64,
+        query: ArchivedThreadQuery,
+    ) -> Paginator<'_, ArchivedThreadPages> {
+        Paginator::new(
+            self,
+            ArchivedThreadPages {
+                channel_id,
+                query,
+                done: false,
+            },
+        )
+    }
+
+    /// Iterate over the users interested in a scheduled event, by increasing
+    /// Id, or by decreasing Id when walking [`before`] a user.
+    ///
+    /// [`before`]: ScheduledEventUserQuery::before
+    pub fn iter_scheduled_event_users(
+        &self,
+        guild_id: u64,
+        event_id: u64,
+        query: ScheduledEventUserQuery,
+    ) -> Paginator<'_, ScheduledEventUserPages> {
+        Paginator::new(
+            self,
+            ScheduledEventUserPages {
+                guild_id,
+                event_id,
+                query,
+                done: false,
+            },
+        )
+    }
+}
+
+fn check_limit(limit: Option<u8>, max: u8) -> Result<()> {
+    match limit {
+        Some(l) if !(1..=max).contains(&l) => {
+            Err(Error::NotInRange("limit", l as u64, 1, max as u64))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::http::{HttpBuilder, MockResponse, MockTransport};
+
+    fn user(id: u64) -> Value {
+        json!({
+            "id": id.to_string(),
+            "username": "user",
+            "discriminator": "0001",
+            "avatar": null,
+        })
+    }
+
+    fn message(id: u64) -> Value {
+        json!({
+            "id": id.to_string(),
+            "channel_id": "1",
+            "author": user(3),
+            "content": "",
+            "timestamp": "2023-01-01T00:00:00.000Z",
+            "edited_timestamp": null,
+            "tts": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": [],
+            "pinned": false,
+            "type": 0,
+        })
+    }
+
+    fn event_user(id: u64) -> Value {
+        json!({ "guild_scheduled_event_id": "2", "user": user(id) })
+    }
+
+    fn page(items: impl IntoIterator<Item = Value>) -> MockResponse {
+        MockResponse::new(200).json(&Value::Array(items.into_iter().collect()))
+    }
+
+    fn client(mock: &MockTransport) -> Http {
+        HttpBuilder::new("token").transport(mock.clone()).build()
+    }
+
+    async fn message_ids(mut messages: Paginator<'_, MessagePages>) -> Vec<u64> {
+        let mut ids = vec![];
+        while let Some(message) = messages.next().await {
+            ids.push(message.unwrap().id.0);
+        }
+
+        ids
+    }
+
+    #[tokio::test]
+    async fn walks_the_messages_after_towards_the_most_recent() {
+        let mock = MockTransport::new();
+        mock.push(page([message(12), message(11)]));
+        mock.push(page([message(13)]));
+        let http = client(&mock);
+
+        let messages = http.iter_messages(1, MessageQuery::new().after(10).limit(2));
+        assert_eq!(message_ids(messages).await, [11, 12, 13]);
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].url.ends_with("?limit=2&after=10"));
+        assert!(requests[1].url.ends_with("?limit=2&after=12"));
+    }
+
+    #[tokio::test]
+    async fn walks_the_messages_before_towards_the_oldest() {
+        let mock = MockTransport::new();
+        mock.push(page([message(9), message(8)]));
+        mock.push(page([message(7)]));
+        let http = client(&mock);
+
+        let messages = http.iter_messages(1, MessageQuery::new().limit(2));
+        assert_eq!(message_ids(messages).await, [9, 8, 7]);
+
+        let requests = mock.requests();
+        assert!(requests[0].url.ends_with("?limit=2&"));
+        assert!(requests[1].url.ends_with("?limit=2&before=8"));
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_max_items() {
+        let mock = MockTransport::new();
+        mock.push(page([message(9), message(8)]));
+        let http = client(&mock);
+
+        let messages = http
+            .iter_messages(1, MessageQuery::new().limit(2))
+            .max_items(1);
+        assert_eq!(message_ids(messages).await, [9]);
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn reverses_the_scheduled_event_users_before() {
+        let mock = MockTransport::new();
+        // Discord returns the users by increasing Id either way
+        mock.push(page([event_user(8), event_user(9)]));
+        mock.push(page([event_user(7)]));
+        let http = client(&mock);
+
+        let query = ScheduledEventUserQuery::new().before(10).limit(2);
+        let users = http
+            .iter_scheduled_event_users(1, 2, query)
+            .try_collect()
+            .await
+            .unwrap();
+        let ids: Vec<u64> = users.iter().map(|u| u.user.id.0).collect();
+        assert_eq!(ids, [9, 8, 7]);
+
+        let requests = mock.requests();
+        assert!(requests[0].url.contains("before=10"));
+        assert!(requests[1].url.contains("before=8"));
+    }
+
+    #[tokio::test]
+    async fn yields_the_error_and_stops() {
+        let mock = MockTransport::new();
+        mock.push(MockResponse::new(403));
+        let http = client(&mock);
+
+        let mut messages = http.iter_messages(1, MessageQuery::new());
+        assert!(matches!(messages.next().await, Some(Err(Error::Http(_)))));
+        assert!(messages.next().await.is_none());
+        assert_eq!(mock.requests().len(), 1);
+    }
+}
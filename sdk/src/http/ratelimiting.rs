@@ -0,0 +1,450 @@
+//! Routes are used for ratelimiting. These are to differentiate between the
+//! different _types_ of routes - such as getting the current user's channels -
+//! for the most part, with the exception being major parameters.
+//!
+//! [Taken from] the Discord docs, major parameters are:
+//!
+//! > Additionally, rate limits take into account major parameters in the URL.
+//! > For example, `/channels/:channel_id` and
+//! > `/channels/:channel_id/messages/:message_id` both take `channel_id` into
+//! > account when generating rate limits since it's the major parameter. The
+//! > only current major parameters are `channel_id`, `guild_id` and
+//! > `webhook_id`.
+//!
+//! This results in the two URIs of `GET /channels/4/messages/7` and
+//! `GET /channels/5/messages/8` being rate limited _separately_. However, the
+//! two URIs of `GET /channels/10/messages/11` and
+//! `GET /channels/10/messages/12` will count towards the "same ratelimit", as
+//! the major parameter - `10` is equivalent in both URIs' format.
+//!
+//! # Examples
+//!
+//! First: taking the first two URIs - `GET /channels/4/messages/7` and
+//! `GET /channels/5/messages/8` - and assuming both buckets have a `limit` of
+//! `10`, requesting the first URI will result in the response containing a
+//! `remaining` of `9`. Immediately after - prior to buckets resetting -
+//! performing a request to the _second_ URI will also contain a `remaining` of
+//! `9` in the response, as the major parameter - `channel_id` - is different
+//! in the two requests (`4` and `5`).
+//!
+//! Second: take for example the last two URIs. Assuming the bucket's `limit` is
+//! `10`, requesting the first URI will return a `remaining` of `9` in the
+//! response. Requesting the second URI will return a `remaining` of `8` in the
+//! response, as the major parameter - `channel_id` - is equivalent for the two
+//! requests (`10`).
+//!
+//! Major parameters are why some variants (i.e. all of the channel/guild
+//! variants) have an associated u64 as data. This is the Id of the parameter,
+//! differentiating between different ratelimits.
+//!
+//! [Taken from]: https://discord.com/developers/docs/topics/rate-limits#rate-limits
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use http_req::response::{Headers, Response, StatusCode};
+use serde::Deserialize;
+use tokio::sync::{Mutex, RwLock};
+use tokio::time::{sleep, Duration};
+use tracing::{debug, instrument};
+
+use super::request::Request;
+use super::routing::Route;
+use super::transport::Transport;
+use super::HttpError;
+use crate::Result;
+
+/// Ratelimiter for requests to the Discord API.
+///
+/// This keeps track of ratelimit data for specific routes as outlined in
+/// [`Route`], using the `X-RateLimit-*` headers returned by Discord, as well
+/// as the global ratelimit.
+///
+/// Requests are delayed before being sent whenever the bucket they belong to
+/// has been exhausted, or while a global ratelimit is in effect. A request
+/// Discord still responds to with a 429 Too Many Requests is not retried
+/// here, the [`Http`] client retries it following its [`RetryPolicy`].
+///
+/// [`Http`]: super::Http
+/// [`RetryPolicy`]: super::RetryPolicy
+#[derive(Clone)]
+pub struct Ratelimiter {
+    // When futures is implemented, make tasks clear out their respective entry
+    // when the 'reset' passes.
+    routes: Arc<RwLock<HashMap<Route, Arc<Mutex<Ratelimit>>>>>,
+    /// When the global ratelimit reported by Discord is over, all the
+    /// requests wait for it before being sent.
+    global: Arc<Mutex<Option<SystemTime>>>,
+}
+
+impl fmt::Debug for Ratelimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ratelimiter")
+            .field("routes", &self.routes)
+            .field("global", &self.global)
+            .finish()
+    }
+}
+
+impl Default for Ratelimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ratelimiter {
+    /// Creates a new ratelimiter with no known buckets.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            routes: Arc::default(),
+            global: Arc::default(),
+        }
+    }
+
+    /// The routes mutex is a HashMap of each [`Route`] and their respective
+    /// ratelimit information.
+    ///
+    /// See the documentation for [`Ratelimit`] for more information on how the
+    /// library handles ratelimiting.
+    #[must_use]
+    pub fn routes(&self) -> Arc<RwLock<HashMap<Route, Arc<Mutex<Ratelimit>>>>> {
+        Arc::clone(&self.routes)
+    }
+
+    /// Performs the request, waiting beforehand if the bucket of its route is
+    /// exhausted, and updates the bucket from the response.
+    ///
+    /// A 429 response is returned as is, once the ratelimit it reports is
+    /// recorded for the next requests.
+    ///
+    /// # Errors
+    ///
+    /// Only error kind that may be returned is [`Error::Http`].
+    ///
+    /// [`Error::Http`]: crate::Error::Http
+    #[instrument(skip(token, transport))]
+    pub async fn perform(
+        &self,
+        req: &mut Request<'_>,
+        token: &str,
+        transport: &dyn Transport,
+    ) -> Result<(Response, String)> {
+        // Wait for the global ratelimit to be over
+        let global = *self.global.lock().await;
+        if let Some(delay) = global.and_then(|reset| reset.duration_since(SystemTime::now()).ok()) {
+            debug!("Globally ratelimited for {}ms", delay.as_millis());
+            sleep(delay).await;
+        }
+
+        // Destructure the tuple instead of retrieving the third value to
+        // take advantage of the type system. If a variant is added that
+        // has a fourth value, the compiler will error.
+        let (_, route, _) = req.route_ref().deconstruct();
+
+        if route == Route::None {
+            return req.build(token, transport).await;
+        }
+
+        // Perform pre-checking here:
+        //
+        // - get the route's relevant rate
+        // - sleep if that route's already rate-limited until the end of the
+        //   'reset' time;
+        // - get the global rate;
+        // - sleep if there is 0 remaining
+        // - then, perform the request
+        let bucket = Arc::clone(self.routes.write().await.entry(route).or_default());
+
+        bucket.lock().await.pre_hook(&route).await;
+
+        let (response, text) = req.build(token, transport).await?;
+
+        // Check if the request got globally ratelimited, and if so hold the
+        // next requests for `retry_after`.
+        //
+        // Otherwise update the bucket's 'limit', 'remaining' and 'reset'
+        // from the 'x-ratelimit-*' headers.
+        if response.headers().get("x-ratelimit-global").is_some() {
+            if let Some(retry_after) = retry_after(&response, &text)? {
+                debug!(
+                    "Globally ratelimited on route {:?} for {}ms",
+                    route,
+                    retry_after.as_millis(),
+                );
+                *self.global.lock().await = Some(SystemTime::now() + retry_after);
+            }
+        } else {
+            bucket
+                .lock()
+                .await
+                .post_hook(&response, &text, &route)
+                .await?;
+        }
+
+        Ok((response, text))
+    }
+}
+
+/// A set of data containing information about the ratelimits for a particular
+/// [`Route`], which is stored in [`Ratelimiter`].
+///
+/// See the [Discord docs] on ratelimits for more information.
+///
+/// **Note**: You should _not_ mutate any of the fields, as this can help cause
+/// 429s.
+///
+/// [Discord docs]: https://discord.com/developers/docs/topics/rate-limits
+#[derive(Debug)]
+pub struct Ratelimit {
+    /// The total number of requests that can be made in a period of time.
+    limit: i64,
+    /// The number of requests remaining in the period of time.
+    remaining: i64,
+    /// The absolute time when the interval resets.
+    reset: Option<SystemTime>,
+    /// The total time when the interval resets.
+    reset_after: Option<Duration>,
+}
+
+impl Ratelimit {
+    #[instrument]
+    pub async fn pre_hook(&mut self, route: &Route) {
+        if self.limit() == 0 {
+            return;
+        }
+
+        let reset = match self.reset {
+            Some(reset) => reset,
+            None => {
+                // We're probably in the past.
+                self.remaining = self.limit;
+
+                return;
+            }
+        };
+
+        let delay = match reset.duration_since(SystemTime::now()) {
+            Ok(delay) => delay,
+            Err(_) => {
+                // if duration is negative (i.e. adequate time has passed since last call to this api)
+                if self.remaining() != 0 {
+                    self.remaining -= 1;
+                }
+
+                return;
+            }
+        };
+
+        if self.remaining() == 0 {
+            debug!(
+                "Pre-emptive ratelimit on route {:?} for {}ms",
+                route,
+                delay.as_millis(),
+            );
+
+            sleep(delay).await;
+
+            return;
+        }
+
+        self.remaining -= 1;
+    }
+
+    /// Updates the ratelimit from the headers of the response.
+    ///
+    /// Returns whether the request was ratelimited, to be retried after its
+    /// `retry_after`.
+    #[instrument(skip(text))]
+    pub async fn post_hook(
+        &mut self,
+        response: &Response,
+        text: &str,
+        route: &Route,
+    ) -> Result<bool> {
+        if let Some(limit) = parse_header(response.headers(), "x-ratelimit-limit")? {
+            self.limit = limit;
+        }
+
+        if let Some(remaining) = parse_header(response.headers(), "x-ratelimit-remaining")? {
+            self.remaining = remaining;
+        }
+
+        if let Some(reset_after) =
+            parse_header::<f64>(response.headers(), "x-ratelimit-reset-after")?
+        {
+            self.reset = Some(SystemTime::now() + Duration::from_secs_f64(reset_after));
+            self.reset_after = Some(Duration::from_secs_f64(reset_after));
+        }
+
+        Ok(if response.status_code() != StatusCode::from(429) {
+            false
+        } else if let Some(retry_after) = retry_after(response, text)? {
+            debug!(
+                "Ratelimited on route {:?} for {}ms",
+                route,
+                retry_after.as_millis(),
+            );
+
+            true
+        } else {
+            false
+        })
+    }
+
+    /// The total number of requests that can be made in a period of time.
+    #[inline]
+    #[must_use]
+    pub const fn limit(&self) -> i64 {
+        self.limit
+    }
+
+    /// The number of requests remaining in the period of time.
+    #[inline]
+    #[must_use]
+    pub const fn remaining(&self) -> i64 {
+        self.remaining
+    }
+
+    /// The absolute time in milliseconds when the interval resets.
+    #[inline]
+    #[must_use]
+    pub const fn reset(&self) -> Option<SystemTime> {
+        self.reset
+    }
+
+    /// The total time in milliseconds when the interval resets.
+    #[inline]
+    #[must_use]
+    pub const fn reset_after(&self) -> Option<Duration> {
+        self.reset_after
+    }
+}
+
+impl Default for Ratelimit {
+    fn default() -> Self {
+        Self {
+            limit: i64::MAX,
+            remaining: i64::MAX,
+            reset: None,
+            reset_after: None,
+        }
+    }
+}
+
+/// Reads how long to wait before retrying a ratelimited request.
+///
+/// Discord sends `retry_after` in the JSON body with sub-second precision, so
+/// that is preferred over the `Retry-After` header which is rounded up to
+/// whole seconds.
+pub(super) fn retry_after(response: &Response, text: &str) -> Result<Option<Duration>> {
+    #[derive(Deserialize)]
+    struct RatelimitBody {
+        retry_after: f64,
+    }
+
+    if let Ok(body) = serde_json::from_str::<RatelimitBody>(text) {
+        return Ok(Some(Duration::from_secs_f64(body.retry_after)));
+    }
+
+    Ok(parse_header::<f64>(response.headers(), "retry-after")?.map(Duration::from_secs_f64))
+}
+
+fn parse_header<T: FromStr>(headers: &Headers, header: &str) -> Result<Option<T>> {
+    match headers.get(header) {
+        Some(value) => match value.trim().parse::<T>() {
+            Ok(v) => Ok(Some(v)),
+            Err(_) => Err(HttpError::RateLimitI64F64.into()),
+        },
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: u16, headers: &[(&str, &str)]) -> Response {
+        let mut head = format!("HTTP/1.1 {} Reason\r\n", status);
+        for (name, value) in headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+
+        Response::from_head(head.as_bytes()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reads_the_bucket_from_the_headers() {
+        let response = response(
+            200,
+            &[
+                ("x-ratelimit-limit", "5"),
+                ("x-ratelimit-remaining", "4"),
+                ("x-ratelimit-reset-after", "1.5"),
+            ],
+        );
+        let mut ratelimit = Ratelimit::default();
+
+        let ratelimited = ratelimit
+            .post_hook(&response, "", &Route::None)
+            .await
+            .unwrap();
+
+        assert!(!ratelimited);
+        assert_eq!(ratelimit.limit(), 5);
+        assert_eq!(ratelimit.remaining(), 4);
+        assert_eq!(ratelimit.reset_after(), Some(Duration::from_millis(1500)));
+        assert!(ratelimit.reset().is_some());
+    }
+
+    #[tokio::test]
+    async fn reports_the_ratelimited_requests() {
+        let response = response(429, &[("x-ratelimit-remaining", "0")]);
+        let mut ratelimit = Ratelimit::default();
+
+        let ratelimited = ratelimit
+            .post_hook(&response, r#"{"retry_after": 0.5}"#, &Route::None)
+            .await
+            .unwrap();
+
+        assert!(ratelimited);
+        assert_eq!(ratelimit.remaining(), 0);
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_headers() {
+        let response = response(200, &[("x-ratelimit-limit", "five")]);
+
+        let result = Ratelimit::default()
+            .post_hook(&response, "", &Route::None)
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn prefers_the_retry_after_of_the_body() {
+        let response = response(429, &[("retry-after", "2")]);
+
+        let body = r#"{"retry_after": 1.25, "global": false}"#;
+        assert_eq!(
+            retry_after(&response, body).unwrap(),
+            Some(Duration::from_millis(1250))
+        );
+        assert_eq!(
+            retry_after(&response, "").unwrap(),
+            Some(Duration::from_secs(2))
+        );
+    }
+
+    #[test]
+    fn has_no_retry_after_without_body_or_header() {
+        let response = response(429, &[]);
+
+        assert_eq!(retry_after(&response, "Too Many Requests").unwrap(), None);
+    }
+}
@@ -1,12 +1,12 @@
-use http_req::{
-    response::{Headers, Response},
-    uri::Uri,
-};
+use http_req::response::{Headers, Response};
 use tracing::instrument;
 
 use super::multipart::Multipart;
 use super::routing::RouteInfo;
-use serenity::{constants, Error, Result};
+use super::transport::{Transport, TransportRequest};
+use serenity::constants;
+
+use crate::Result;
 
 pub struct RequestBuilder<'a> {
     body: Option<&'a [u8]>,
@@ -82,57 +82,64 @@ impl<'a> Request<'a> {
         }
     }
 
-    #[instrument(skip(token))]
-    pub async fn build(&mut self, token: &str) -> Result<(Response, String)> {
+    /// Send the request through the `transport`, authorized with the
+    /// `token`.
+    #[instrument(skip(token, transport))]
+    pub async fn build(
+        &mut self,
+        token: &str,
+        transport: &dyn Transport,
+    ) -> Result<(Response, String)> {
         let Request {
             body,
-            multipart: ref mut _multipart,
+            ref mut multipart,
             headers: ref request_headers,
             route: ref route_info,
         } = *self;
 
-        let (method, _, path) = route_info.deconstruct();
-
-        let uri: Uri = Uri::try_from(path.as_ref()).unwrap();
-        let mut req = http_req::request::Request::new(&uri);
-        req.method(method.reqwest_method());
-
-        if let Some(bytes) = body {
-            req.body(bytes);
-        }
-
-        req.header("User-Agent", constants::USER_AGENT);
-        req.header("Authorization", token);
-
-        // Discord will return a 400: Bad Request response if we set the content type header,
-        // but don't give a body.
-        if self.body.is_some() {
-            req.header("Content-Type", "application/json");
-        }
+        let (method, route, path) = route_info.deconstruct();
+
+        let mut headers = Headers::new();
+        headers.insert("User-Agent", constants::USER_AGENT);
+        headers.insert("Authorization", token);
+        crate::insert_flows_jwt(&mut headers);
+
+        let form = match multipart {
+            Some(multipart) => Some(multipart.build_form()?),
+            None => None,
+        };
+
+        let body = if let Some(ref form) = form {
+            headers.insert("Content-Type", &form.content_type());
+            headers.insert("Content-Length", &form.body.len().to_string());
+            &form.body[..]
+        } else {
+            if body.is_some() {
+                // Discord will return a 400: Bad Request response if we set the content type header,
+                // but don't give a body.
+                headers.insert("Content-Type", "application/json");
+            }
 
-        // if let Some(multipart) = multipart {
-        // Setting multipart adds the content-length header
-        // builder = builder.multipart(multipart.build_form().await?);
-        // } else {
-        req.header(
-            "Content-Length",
-            &body.unwrap_or(&Vec::new()).len().to_string(),
-        );
-        // }
+            let body = body.unwrap_or_default();
+            headers.insert("Content-Length", &body.len().to_string());
+            body
+        };
 
         if let Some(ref request_headers) = request_headers {
             for (k, v) in request_headers.iter() {
-                req.header(k, v);
+                headers.insert(k, v);
             }
         }
 
-        let mut writer = Vec::new();
-        let res = req
-            .send(&mut writer)
-            .map_err(|e| Error::Url(e.to_string()))?;
-        let text = String::from_utf8(writer).map_err(|e| Error::Url(e.to_string()))?;
-
-        Ok((res, text))
+        transport
+            .send(TransportRequest {
+                method,
+                route,
+                url: &path,
+                headers: &headers,
+                body,
+            })
+            .await
     }
 
     #[must_use]
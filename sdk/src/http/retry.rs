@@ -0,0 +1,316 @@
+//! Retrying the requests which failed transiently.
+//!
+//! An [`Http`] client retries a request when it could not be sent or read,
+//! or when Discord answers with a 5xx or a 429, following its
+//! [`RetryPolicy`]. A 429 is always safe to retry, the request was not
+//! processed, and waits for the `retry_after` sent by Discord. The other
+//! failures may happen after Discord processed the request, so by default
+//! only the idempotent requests (`GET`, `PUT` and `DELETE`) are retried then:
+//! a [`RouteInfo::CreateMessage`] answered with a 502 is not sent again, as
+//! the message may have been posted anyway.
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//!
+//! use discord_flows::http::{HttpBuilder, RetryPolicy};
+//!
+//! let policy = RetryPolicy::new()
+//!     .max_retries(3)
+//!     .base_delay(Duration::from_millis(200))
+//!     .on_attempt(|attempt| {
+//!         eprintln!("Retrying {} in {:?}", attempt.url, attempt.delay);
+//!     });
+//! let http = HttpBuilder::new("token").retry_policy(policy).build();
+//! ```
+//!
+//! [`Http`]: super::Http
+//! [`RouteInfo::CreateMessage`]: super::routing::RouteInfo::CreateMessage
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{HttpError, LightMethod};
+use crate::Error;
+
+/// Why a request is retried.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RetryReason<'a> {
+    /// The request could not be sent or its response could not be read.
+    Transport(&'a Error),
+    /// Discord answered with this 5xx status.
+    ServerError(u16),
+    /// Discord answered with a 429 Too Many Requests.
+    Ratelimited,
+}
+
+/// A retry about to be made, given to the [`RetryPolicy::on_attempt`] hook.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct RetryAttempt<'a> {
+    pub method: LightMethod,
+    pub url: &'a str,
+    /// The number of the retry, starting at 1.
+    pub retry: u32,
+    pub reason: RetryReason<'a>,
+    /// How long the client waits before sending the request again.
+    pub delay: Duration,
+}
+
+type AttemptHook = Arc<dyn Fn(&RetryAttempt<'_>) + Send + Sync>;
+
+/// When and how soon an [`Http`] client retries a failed request, see the
+/// [module documentation](self).
+///
+/// The default policy retries once, after half a second or the `retry_after`
+/// of a 429.
+///
+/// [`Http`]: super::Http
+#[derive(Clone)]
+#[must_use]
+pub struct RetryPolicy {
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    retry_non_idempotent: bool,
+    on_attempt: Option<AttemptHook>,
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_retries", &self.max_retries)
+            .field("base_delay", &self.base_delay)
+            .field("max_delay", &self.max_delay)
+            .field("retry_non_idempotent", &self.retry_non_idempotent)
+            .finish()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 1,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            retry_non_idempotent: false,
+            on_attempt: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A policy never retrying the requests.
+    pub fn never() -> Self {
+        Self::new().max_retries(0)
+    }
+
+    /// Sets how many times a request is retried at most.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+
+        self
+    }
+
+    /// Sets the delay before the first retry, doubled for each of the
+    /// following ones.
+    pub fn base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+
+        self
+    }
+
+    /// Sets the longest delay before a retry. A request ratelimited for
+    /// longer than that is not retried.
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+
+        self
+    }
+
+    /// Sets whether the `POST` and `PATCH` requests are retried after a
+    /// transport error or a 5xx, at the risk of performing them twice.
+    pub fn retry_non_idempotent(mut self, retry_non_idempotent: bool) -> Self {
+        self.retry_non_idempotent = retry_non_idempotent;
+
+        self
+    }
+
+    /// Sets a hook called before each retry, to log it for instance.
+    pub fn on_attempt(mut self, hook: impl Fn(&RetryAttempt<'_>) + Send + Sync + 'static) -> Self {
+        self.on_attempt = Some(Arc::new(hook));
+
+        self
+    }
+
+    /// The delay before retrying the request, `None` when it must not be
+    /// retried.
+    ///
+    /// `retry` is the number of the retry and `retry_after` the delay asked
+    /// by Discord, if any.
+    pub(crate) fn delay(
+        &self,
+        method: LightMethod,
+        url: &str,
+        retry: u32,
+        reason: RetryReason<'_>,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        if retry > self.max_retries {
+            return None;
+        }
+
+        let idempotent = matches!(
+            method,
+            LightMethod::Get | LightMethod::Put | LightMethod::Delete
+        );
+        if !idempotent && !self.retry_non_idempotent && !matches!(reason, RetryReason::Ratelimited)
+        {
+            return None;
+        }
+
+        let delay = retry_after.unwrap_or_else(|| {
+            self.base_delay
+                .saturating_mul(2u32.saturating_pow(retry - 1))
+                .min(self.max_delay)
+        });
+        if delay > self.max_delay {
+            return None;
+        }
+
+        if let Some(hook) = &self.on_attempt {
+            hook(&RetryAttempt {
+                method,
+                url,
+                retry,
+                reason,
+                delay,
+            });
+        }
+
+        Some(delay)
+    }
+}
+
+/// Whether the request failed to be sent or its response to be read.
+pub(super) fn is_transport_error(e: &Error) -> bool {
+    match e {
+        Error::Io(_) => true,
+        Error::Http(e) => matches!(**e, HttpError::Request(_)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::http::{HttpBuilder, MockResponse, MockTransport};
+
+    fn client(mock: &MockTransport, policy: RetryPolicy) -> crate::http::Http {
+        HttpBuilder::new("token")
+            .ratelimiter_disabled(true)
+            .retry_policy(policy.base_delay(Duration::from_millis(1)))
+            .transport(mock.clone())
+            .build()
+    }
+
+    #[tokio::test]
+    async fn retries_the_idempotent_requests() {
+        let mock = MockTransport::new();
+        mock.push(MockResponse::new(502));
+        mock.push(MockResponse::new(200).json(&json!([])));
+        let retries = Arc::new(Mutex::new(vec![]));
+        let hook_retries = Arc::clone(&retries);
+        let policy = RetryPolicy::new().on_attempt(move |attempt| {
+            hook_retries.lock().unwrap().push(attempt.retry);
+        });
+
+        let roles = client(&mock, policy).get_guild_roles(1).await.unwrap();
+
+        assert!(roles.is_empty());
+        assert_eq!(mock.requests().len(), 2);
+        assert_eq!(*retries.lock().unwrap(), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn does_not_resend_messages_after_a_server_error() {
+        let mock = MockTransport::new();
+        mock.push(MockResponse::new(500));
+        mock.push(MockResponse::new(200).json(&json!({})));
+
+        let message = json!({ "content": "hello" });
+        let result = client(&mock, RetryPolicy::new().max_retries(3))
+            .send_message(1, &message)
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_ratelimited_requests_after_retry_after() {
+        let mock = MockTransport::new();
+        mock.push(MockResponse::new(429).json(&json!({ "retry_after": 0.01, "global": false })));
+        mock.push(MockResponse::new(204));
+
+        let response = json!({ "type": 4, "data": { "content": "pong" } });
+        client(&mock, RetryPolicy::new())
+            .create_interaction_response(1, "abc", &response)
+            .await
+            .unwrap();
+
+        assert_eq!(mock.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn follows_the_policy_for_the_ratelimited_requests() {
+        let mock = MockTransport::new();
+        let ratelimited = || {
+            MockResponse::new(429)
+                .header("x-ratelimit-global", "true")
+                .json(&json!({ "retry_after": 0.01, "global": true }))
+        };
+        mock.push(ratelimited());
+        mock.push(ratelimited());
+        let delays = Arc::new(Mutex::new(vec![]));
+        let hook_delays = Arc::clone(&delays);
+        let policy = RetryPolicy::new().on_attempt(move |attempt| {
+            hook_delays.lock().unwrap().push(attempt.delay);
+        });
+        let http = HttpBuilder::new("token")
+            .retry_policy(policy)
+            .transport(mock.clone())
+            .build();
+
+        let result = http.get_guild_roles(1).await;
+
+        match result {
+            Err(Error::Http(e)) => assert_eq!(e.status_code().map(u16::from), Some(429)),
+            _ => panic!("expected a 429, got {:?}", result),
+        }
+        assert_eq!(mock.requests().len(), 2);
+        assert_eq!(*delays.lock().unwrap(), vec![Duration::from_millis(10)]);
+    }
+
+    #[tokio::test]
+    async fn does_not_wait_longer_than_the_max_delay() {
+        let mock = MockTransport::new();
+        mock.push(MockResponse::new(429).json(&json!({ "retry_after": 60.0, "global": false })));
+        let http = HttpBuilder::new("token")
+            .retry_policy(RetryPolicy::new().max_delay(Duration::from_secs(1)))
+            .transport(mock.clone())
+            .build();
+
+        assert!(http.get_guild_roles(1).await.is_err());
+        assert_eq!(mock.requests().len(), 1);
+    }
+}
@@ -6,6 +6,7 @@ use crate::http::utils::status;
 use super::utils::api;
 use super::LightMethod;
 use serenity::constants;
+use serenity::model::Timestamp;
 
 /// A representation of all routes registered within the library. These are safe
 /// and memory-efficient representations of each path that functions exist for
@@ -626,13 +627,13 @@ impl Route {
     #[must_use]
     pub fn channel_archived_public_threads(
         channel_id: u64,
-        before: Option<u64>,
+        before: Option<Timestamp>,
         limit: Option<u64>,
     ) -> String {
-        let mut s = api!("/channels/{}/threads/archived/public", channel_id);
+        let mut s = api!("/channels/{}/threads/archived/public?", channel_id);
 
-        if let Some(id) = before {
-            write!(s, "&before={}", id).unwrap();
+        if let Some(timestamp) = before {
+            write!(s, "&before={}", timestamp).unwrap();
         }
 
         if let Some(limit) = limit {
@@ -645,13 +646,13 @@ impl Route {
     #[must_use]
     pub fn channel_archived_private_threads(
         channel_id: u64,
-        before: Option<u64>,
+        before: Option<Timestamp>,
         limit: Option<u64>,
     ) -> String {
-        let mut s = api!("/channels/{}/threads/archived/private", channel_id);
+        let mut s = api!("/channels/{}/threads/archived/private?", channel_id);
 
-        if let Some(id) = before {
-            write!(s, "&before={}", id).unwrap();
+        if let Some(timestamp) = before {
+            write!(s, "&before={}", timestamp).unwrap();
         }
 
         if let Some(limit) = limit {
@@ -668,7 +669,7 @@ impl Route {
         limit: Option<u64>,
     ) -> String {
         let mut s = api!(
-            "/channels/{}/users/@me/threads/archived/private",
+            "/channels/{}/users/@me/threads/archived/private?",
             channel_id
         );
 
@@ -1168,6 +1169,14 @@ impl Route {
         api!("/applications/{}/commands", application_id)
     }
 
+    #[must_use]
+    pub fn application_commands_with_localizations(application_id: u64) -> String {
+        api!(
+            "/applications/{}/commands?with_localizations=true",
+            application_id
+        )
+    }
+
     #[must_use]
     pub fn application_guild_command(
         application_id: u64,
@@ -1205,6 +1214,18 @@ impl Route {
         )
     }
 
+    #[must_use]
+    pub fn application_guild_commands_with_localizations(
+        application_id: u64,
+        guild_id: u64,
+    ) -> String {
+        api!(
+            "/applications/{}/guilds/{}/commands?with_localizations=true",
+            application_id,
+            guild_id
+        )
+    }
+
     #[must_use]
     pub fn application_guild_commands_permissions(application_id: u64, guild_id: u64) -> String {
         api!(
@@ -1583,12 +1604,12 @@ pub enum RouteInfo<'a> {
     },
     GetChannelArchivedPublicThreads {
         channel_id: u64,
-        before: Option<u64>,
+        before: Option<Timestamp>,
         limit: Option<u64>,
     },
     GetChannelArchivedPrivateThreads {
         channel_id: u64,
-        before: Option<u64>,
+        before: Option<Timestamp>,
         limit: Option<u64>,
     },
     GetChannelJoinedPrivateArchivedThreads {
@@ -1614,6 +1635,9 @@ pub enum RouteInfo<'a> {
     GetGlobalApplicationCommands {
         application_id: u64,
     },
+    GetGlobalApplicationCommandsWithLocalizations {
+        application_id: u64,
+    },
     GetGlobalApplicationCommand {
         application_id: u64,
         command_id: u64,
@@ -1628,6 +1652,10 @@ pub enum RouteInfo<'a> {
         application_id: u64,
         guild_id: u64,
     },
+    GetGuildApplicationCommandsWithLocalizations {
+        application_id: u64,
+        guild_id: u64,
+    },
     GetGuildApplicationCommand {
         application_id: u64,
         guild_id: u64,
@@ -2567,6 +2595,13 @@ impl<'a> RouteInfo<'a> {
                 Route::ApplicationsIdCommands(application_id),
                 Cow::from(Route::application_commands(application_id)),
             ),
+            RouteInfo::GetGlobalApplicationCommandsWithLocalizations { application_id } => (
+                LightMethod::Get,
+                Route::ApplicationsIdCommands(application_id),
+                Cow::from(Route::application_commands_with_localizations(
+                    application_id,
+                )),
+            ),
             RouteInfo::GetGlobalApplicationCommand {
                 application_id,
                 command_id,
@@ -2593,6 +2628,17 @@ impl<'a> RouteInfo<'a> {
                 Route::ApplicationsIdGuildsIdCommands(application_id),
                 Cow::from(Route::application_guild_commands(application_id, guild_id)),
             ),
+            RouteInfo::GetGuildApplicationCommandsWithLocalizations {
+                application_id,
+                guild_id,
+            } => (
+                LightMethod::Get,
+                Route::ApplicationsIdGuildsIdCommands(application_id),
+                Cow::from(Route::application_guild_commands_with_localizations(
+                    application_id,
+                    guild_id,
+                )),
+            ),
             RouteInfo::GetGuildApplicationCommand {
                 application_id,
                 guild_id,
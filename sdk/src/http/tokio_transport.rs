@@ -0,0 +1,425 @@
+//! A non-blocking [`Transport`] driven by the tokio runtime of the flow.
+//!
+//! The requests are written as HTTP/1.1 on `tokio` sockets, so concurrent
+//! requests of a flow overlap instead of running one after another, and the
+//! connections are kept alive and reused by the following requests to the
+//! same host.
+//!
+//! In WasmEdge, the `https` connections are encrypted by the rustls plugin
+//! of the runtime over the same non-blocking sockets. In the native builds,
+//! for the tests, the `https` requests are sent by the blocking `http_req`
+//! on a thread of the blocking pool of the runtime.
+//!
+//! Connecting, TLS handshake included, gives up after [`CONNECT_TIMEOUT`],
+//! and reading the response after [`RESPONSE_TIMEOUT`].
+//!
+//! [`CONNECT_TIMEOUT`]: super::CONNECT_TIMEOUT
+//! [`RESPONSE_TIMEOUT`]: super::RESPONSE_TIMEOUT
+
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use async_trait::async_trait;
+use http_req::response::Response;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, ReadBuf,
+};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use url::Url;
+#[cfg(target_arch = "wasm32")]
+use wasmedge_rustls_api::{stream::async_stream::TlsStream, ClientConfig};
+
+use super::transport::{Transport, TransportRequest, CONNECT_TIMEOUT, RESPONSE_TIMEOUT};
+use super::LightMethod;
+use crate::{Error, Result};
+
+/// The most idle connections kept to a host.
+const IDLE_CONNECTIONS_PER_HOST: usize = 16;
+
+type Connection = BufReader<Stream>;
+
+/// A connection, encrypted for the `https` hosts.
+#[derive(Debug)]
+enum Stream {
+    Plain(TcpStream),
+    #[cfg(target_arch = "wasm32")]
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(target_arch = "wasm32")]
+            Stream::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(target_arch = "wasm32")]
+            Stream::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(target_arch = "wasm32")]
+            Stream::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(target_arch = "wasm32")]
+            Stream::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
+
+/// A [`Transport`] sending the requests without blocking the runtime,
+/// over connections reused between requests, see the
+/// [module documentation](self).
+///
+/// The clones of a transport share their connections.
+#[derive(Clone, Debug, Default)]
+pub struct TokioTransport {
+    idle: Arc<Mutex<HashMap<String, Vec<Connection>>>>,
+}
+
+impl TokioTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn take_idle(&self, origin: &str) -> Option<Connection> {
+        self.idle.lock().unwrap().get_mut(origin)?.pop()
+    }
+
+    fn put_idle(&self, origin: &str, connection: Connection) {
+        let mut idle = self.idle.lock().unwrap();
+        let connections = idle.entry(origin.to_string()).or_default();
+        if connections.len() < IDLE_CONNECTIONS_PER_HOST {
+            connections.push(connection);
+        }
+    }
+
+    async fn send_on_socket(
+        &self,
+        url: &Url,
+        request: &TransportRequest<'_>,
+    ) -> Result<(Response, String)> {
+        let host = url.host_str().ok_or_else(|| Error::Url(url.to_string()))?;
+        let port = url.port_or_known_default().unwrap_or(80);
+        let origin = format!("{}://{}:{}", url.scheme(), host, port);
+        let head = request_head(url, request);
+
+        // An idle connection may have been closed by the server in the
+        // meantime, the request is then sent again on a new one
+        if let Some(mut connection) = self.take_idle(&origin) {
+            if let Some(exchange) = timed_exchange(&mut connection, &head, request).await? {
+                return self.finish(&origin, connection, exchange);
+            }
+        }
+
+        let stream = timeout(CONNECT_TIMEOUT, connect(url.scheme(), host, port))
+            .await
+            .map_err(|_| timed_out("Connecting", host))??;
+        let mut connection = BufReader::new(stream);
+        match timed_exchange(&mut connection, &head, request).await? {
+            Some(exchange) => self.finish(&origin, connection, exchange),
+            None => Err(Error::Url(format!("{} closed the connection", host))),
+        }
+    }
+
+    fn finish(
+        &self,
+        origin: &str,
+        connection: Connection,
+        (response, text, reusable): (Response, String, bool),
+    ) -> Result<(Response, String)> {
+        if reusable {
+            self.put_idle(origin, connection);
+        }
+
+        Ok((response, text))
+    }
+}
+
+#[async_trait]
+impl Transport for TokioTransport {
+    async fn send(&self, request: TransportRequest<'_>) -> Result<(Response, String)> {
+        let url = Url::parse(request.url).map_err(|e| Error::Url(e.to_string()))?;
+
+        match url.scheme() {
+            "http" => self.send_on_socket(&url, &request).await,
+            #[cfg(target_arch = "wasm32")]
+            "https" => self.send_on_socket(&url, &request).await,
+            #[cfg(not(target_arch = "wasm32"))]
+            "https" => send_on_blocking_pool(&request).await,
+            scheme => Err(Error::Url(format!("Unsupported scheme {}", scheme))),
+        }
+    }
+}
+
+async fn connect(scheme: &str, host: &str, port: u16) -> Result<Stream> {
+    let stream = TcpStream::connect((host, port)).await?;
+
+    match scheme {
+        #[cfg(target_arch = "wasm32")]
+        "https" => {
+            let stream = TlsStream::connect(&ClientConfig::default(), host, stream)
+                .await
+                .map_err(|(e, _)| e)?;
+            Ok(Stream::Tls(Box::new(stream)))
+        }
+        _ => Ok(Stream::Plain(stream)),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn send_on_blocking_pool(request: &TransportRequest<'_>) -> Result<(Response, String)> {
+    let (method, route) = (request.method, request.route);
+    let (url, headers, body) = (
+        request.url.to_string(),
+        request.headers.clone(),
+        request.body.to_vec(),
+    );
+
+    tokio::task::spawn_blocking(move || {
+        super::transport::send_blocking(TransportRequest {
+            method,
+            route,
+            url: &url,
+            headers: &headers,
+            body: &body,
+        })
+    })
+    .await
+    .map_err(|e| Error::Io(io::Error::other(e)))?
+}
+
+fn timed_out(what: &str, to: &str) -> Error {
+    Error::Io(io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("{} to {} timed out", what, to),
+    ))
+}
+
+async fn timed_exchange(
+    connection: &mut Connection,
+    head: &str,
+    request: &TransportRequest<'_>,
+) -> Result<Option<(Response, String, bool)>> {
+    timeout(RESPONSE_TIMEOUT, exchange(connection, head, request))
+        .await
+        .map_err(|_| timed_out("Sending", request.url))?
+}
+
+fn request_head(url: &Url, request: &TransportRequest<'_>) -> String {
+    let method = match request.method {
+        LightMethod::Delete => "DELETE",
+        LightMethod::Get => "GET",
+        LightMethod::Patch => "PATCH",
+        LightMethod::Post => "POST",
+        LightMethod::Put => "PUT",
+    };
+    let target = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+
+    let mut head = format!("{} {} HTTP/1.1\r\nHost: {}\r\n", method, target, host);
+    let mut has_length = false;
+    for (k, v) in request.headers.iter() {
+        has_length |= k.eq_ignore_ascii_case("content-length");
+        head.push_str(&format!("{}: {}\r\n", k, v));
+    }
+    if !has_length {
+        head.push_str(&format!("Content-Length: {}\r\n", request.body.len()));
+    }
+    head.push_str("\r\n");
+
+    head
+}
+
+/// Send the request on the connection and read its response, with whether
+/// the connection can be reused.
+///
+/// Returns `None` when the connection was closed before the response
+/// started.
+async fn exchange(
+    connection: &mut Connection,
+    head: &str,
+    request: &TransportRequest<'_>,
+) -> Result<Option<(Response, String, bool)>> {
+    let stream = connection.get_mut();
+    if stream.write_all(head.as_bytes()).await.is_err() {
+        return Ok(None);
+    }
+    stream.write_all(request.body).await?;
+    stream.flush().await?;
+
+    let mut response_head = Vec::new();
+    loop {
+        let start = response_head.len();
+        if connection.read_until(b'\n', &mut response_head).await? == 0 {
+            if response_head.is_empty() {
+                return Ok(None);
+            }
+            return Err(Error::Url(String::from("Truncated response head")));
+        }
+        if &response_head[start..] == b"\r\n" || &response_head[start..] == b"\n" {
+            break;
+        }
+    }
+
+    let response = Response::from_head(&response_head)?;
+    let status = u16::from(response.status_code());
+    let header = |name: &str| response.headers().get(name).map(|v| v.to_ascii_lowercase());
+    let mut reusable = header("connection").as_deref() != Some("close");
+
+    let body = if (100..200).contains(&status) || status == 204 || status == 304 {
+        vec![]
+    } else if header("transfer-encoding").is_some_and(|v| v.contains("chunked")) {
+        read_chunked(connection).await?
+    } else if let Some(length) = header("content-length") {
+        let length = length
+            .trim()
+            .parse::<usize>()
+            .map_err(|e| Error::Url(e.to_string()))?;
+        let mut body = vec![0; length];
+        connection.read_exact(&mut body).await?;
+        body
+    } else {
+        // The end of the body is the end of the connection
+        reusable = false;
+        let mut body = Vec::new();
+        connection.read_to_end(&mut body).await?;
+        body
+    };
+
+    let text = String::from_utf8(body).map_err(|e| Error::Url(e.to_string()))?;
+
+    Ok(Some((response, text, reusable)))
+}
+
+async fn read_chunked(connection: &mut Connection) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        connection.read_line(&mut line).await?;
+        let size = line.trim().split(';').next().unwrap_or_default();
+        let size = usize::from_str_radix(size, 16).map_err(|e| Error::Url(e.to_string()))?;
+
+        if size == 0 {
+            // Skip the trailers up to the empty line ending the body
+            loop {
+                line.clear();
+                if connection.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+                    return Ok(body);
+                }
+            }
+        }
+
+        let start = body.len();
+        body.resize(start + size, 0);
+        connection.read_exact(&mut body[start..]).await?;
+        line.clear();
+        connection.read_line(&mut line).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use http_req::response::Headers;
+    use tokio::net::TcpListener;
+    use tokio::time::timeout;
+
+    use super::*;
+    use crate::http::routing::Route;
+
+    #[tokio::test]
+    async fn reuses_the_connection() {
+        let responses = [
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nfirst",
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nsec\r\n3\r\nond\r\n0\r\n\r\n",
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n",
+        ];
+
+        // A server answering the requests on a single connection
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/path?query", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut requests = vec![];
+            for response in responses {
+                let mut head = String::new();
+                while !head.ends_with("\r\n\r\n") {
+                    stream.read_line(&mut head).await.unwrap();
+                }
+                requests.push(head);
+                stream
+                    .get_mut()
+                    .write_all(response.as_bytes())
+                    .await
+                    .unwrap();
+            }
+            requests
+        });
+
+        let transport = TokioTransport::new();
+        let headers = Headers::new();
+        let mut results = vec![];
+        for _ in 0..responses.len() {
+            let request = TransportRequest {
+                method: LightMethod::Get,
+                route: Route::None,
+                url: &url,
+                headers: &headers,
+                body: &[],
+            };
+            let send = timeout(Duration::from_secs(5), transport.send(request));
+            let (response, text) = send.await.expect("a new connection").unwrap();
+            results.push((u16::from(response.status_code()), text));
+        }
+
+        assert_eq!(
+            results,
+            vec![
+                (200, String::from("first")),
+                (200, String::from("second")),
+                (404, String::new()),
+            ]
+        );
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("GET /path?query HTTP/1.1\r\n"));
+        assert!(requests[0].contains("Content-Length: 0\r\n"));
+    }
+}
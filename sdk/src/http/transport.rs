@@ -0,0 +1,319 @@
+//! The transport sending the requests of an [`Http`] client.
+//!
+//! [`Http`] sends its requests through a shared [`TokioTransport`] unless
+//! another [`Transport`] is given to [`HttpBuilder::transport`]. [`MockTransport`]
+//! records the requests and answers them with scripted responses, so that
+//! the code calling [`Http`] can be tested without a network:
+//!
+//! ```rust,no_run
+//! # async fn run() -> discord_flows::Result<()> {
+//! use discord_flows::http::{HttpBuilder, LightMethod, MockResponse, MockTransport};
+//! use serde_json::json;
+//!
+//! let mock = MockTransport::new();
+//! mock.push(MockResponse::new(204));
+//!
+//! let http = HttpBuilder::new("token").transport(mock.clone()).build();
+//! http.delete_message(123, 456).await?;
+//!
+//! let requests = mock.requests();
+//! assert_eq!(requests[0].method, LightMethod::Delete);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Http`]: super::Http
+//! [`HttpBuilder::transport`]: super::HttpBuilder::transport
+//! [`TokioTransport`]: super::TokioTransport
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use http_req::{
+    response::{Headers, Response},
+    uri::Uri,
+};
+use serde_json::Value;
+
+use super::routing::Route;
+use super::LightMethod;
+use crate::{Error, Result};
+
+/// How long connecting to a host may take, TLS handshake included.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the response may take, from the request being sent to the end
+/// of its body.
+pub const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A request ready to be sent, with all its headers.
+#[derive(Clone, Debug)]
+pub struct TransportRequest<'a> {
+    pub method: LightMethod,
+    /// The route of the request, used for ratelimiting.
+    pub route: Route,
+    pub url: &'a str,
+    pub headers: &'a Headers,
+    /// The body of the request, empty when it has none.
+    pub body: &'a [u8],
+}
+
+/// Sends the requests of an [`Http`] client and reads their responses.
+///
+/// [`Http`]: super::Http
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Send the request and read the whole response, whatever its status.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error only when no response could be read.
+    async fn send(&self, request: TransportRequest<'_>) -> Result<(Response, String)>;
+}
+
+/// A [`Transport`] sending the requests with `http_req`, blocking the
+/// runtime until their response is read.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HttpReqTransport;
+
+#[async_trait]
+impl Transport for HttpReqTransport {
+    async fn send(&self, request: TransportRequest<'_>) -> Result<(Response, String)> {
+        send_blocking(request)
+    }
+}
+
+/// Send the request with `http_req`, blocking until the response is read.
+pub(crate) fn send_blocking(request: TransportRequest<'_>) -> Result<(Response, String)> {
+    let uri: Uri = Uri::try_from(request.url).map_err(|e| Error::Url(e.to_string()))?;
+    let mut req = http_req::request::Request::new(&uri);
+    req.method(request.method.reqwest_method())
+        .connect_timeout(Some(CONNECT_TIMEOUT))
+        .timeout(Some(RESPONSE_TIMEOUT));
+
+    for (k, v) in request.headers.iter() {
+        req.header(k, v);
+    }
+    if !request.body.is_empty() {
+        req.body(request.body);
+    }
+
+    let mut writer = Vec::new();
+    let res = req.send(&mut writer)?;
+    let text = String::from_utf8(writer).map_err(|e| Error::Url(e.to_string()))?;
+
+    Ok((res, text))
+}
+
+/// A request recorded by a [`MockTransport`].
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: LightMethod,
+    pub route: Route,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    /// The body parsed as JSON, `None` when it is empty or not JSON.
+    pub fn json(&self) -> Option<Value> {
+        serde_json::from_slice(&self.body).ok()
+    }
+
+    /// The value of the header `name`, case insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A response scripted on a [`MockTransport`].
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl MockResponse {
+    /// A response with the `status` and an empty body.
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: String::new(),
+        }
+    }
+
+    /// Set the body to the `value` serialized as JSON.
+    pub fn json(mut self, value: &Value) -> Self {
+        self.body = value.to_string();
+
+        self.header("Content-Type", "application/json")
+    }
+
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into();
+
+        self
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+
+        self
+    }
+
+    fn into_response(self) -> Result<(Response, String)> {
+        let mut head = format!("HTTP/1.1 {} Mock\r\n", self.status);
+        for (k, v) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", k, v));
+        }
+        head.push_str("\r\n");
+
+        let response = Response::from_head(head.as_bytes())?;
+
+        Ok((response, self.body))
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    requests: Vec<RecordedRequest>,
+    responses: VecDeque<MockResponse>,
+}
+
+/// A [`Transport`] recording the requests and answering them with the
+/// scripted responses, in order.
+///
+/// Once the scripted responses are exhausted, the requests are answered
+/// with a 404. The clones of a mock share their requests and responses, so
+/// one of them can be given to the [`Http`] client and the other one kept to
+/// make assertions.
+///
+/// [`Http`]: super::Http
+#[derive(Clone, Debug, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Script the response of the next unanswered request.
+    pub fn push(&self, response: MockResponse) {
+        self.state.lock().unwrap().responses.push_back(response);
+    }
+
+    /// The requests sent so far, in order.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Record the request and answer it, without waiting.
+    pub(crate) fn respond(&self, request: TransportRequest<'_>) -> Result<(Response, String)> {
+        let response = {
+            let mut state = self.state.lock().unwrap();
+            state.requests.push(RecordedRequest {
+                method: request.method,
+                route: request.route,
+                url: request.url.to_string(),
+                headers: request
+                    .headers
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.clone()))
+                    .collect(),
+                body: request.body.to_vec(),
+            });
+            state.responses.pop_front()
+        };
+
+        response
+            .unwrap_or_else(|| {
+                MockResponse::new(404).json(&serde_json::json!({
+                    "code": 0,
+                    "message": "No response scripted",
+                }))
+            })
+            .into_response()
+    }
+}
+
+#[async_trait]
+impl Transport for MockTransport {
+    async fn send(&self, request: TransportRequest<'_>) -> Result<(Response, String)> {
+        self.respond(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::http::HttpBuilder;
+
+    fn client(mock: &MockTransport) -> crate::http::Http {
+        HttpBuilder::new("token").transport(mock.clone()).build()
+    }
+
+    #[tokio::test]
+    async fn records_the_requests() {
+        let mock = MockTransport::new();
+        mock.push(MockResponse::new(204));
+        let http = client(&mock);
+
+        let response = json!({ "type": 4, "data": { "content": "pong" } });
+        http.create_interaction_response(1, "abc", &response)
+            .await
+            .unwrap();
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, LightMethod::Post);
+        assert_eq!(requests[0].route, Route::InteractionsId(1));
+        assert!(requests[0].url.ends_with("/interactions/1/abc/callback"));
+        assert_eq!(requests[0].header("authorization"), Some("Bot token"));
+        assert_eq!(requests[0].json(), Some(response));
+    }
+
+    #[tokio::test]
+    async fn returns_the_scripted_responses() {
+        let mock = MockTransport::new();
+        mock.push(MockResponse::new(200).json(&json!([{ "name": "ping" }])));
+        let http = HttpBuilder::new("token")
+            .application_id(7)
+            .transport(mock.clone())
+            .build();
+
+        let commands = http
+            .get_global_application_commands_with_localizations()
+            .await
+            .unwrap();
+        assert_eq!(commands, vec![json!({ "name": "ping" })]);
+
+        let requests = mock.requests();
+        assert_eq!(requests[0].method, LightMethod::Get);
+        assert!(requests[0].body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fails_on_unsuccessful_responses() {
+        let mock = MockTransport::new();
+        let http = client(&mock);
+
+        match http.delete_message(1, 2).await.unwrap_err() {
+            Error::Http(e) => assert_eq!(e.status_code().map(u16::from), Some(404)),
+            e => panic!("unexpected error {}", e),
+        }
+        assert_eq!(mock.requests().len(), 1);
+    }
+}
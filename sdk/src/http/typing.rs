@@ -6,7 +6,7 @@ use tokio::sync::oneshot::{self, Sender};
 use tokio::time::{sleep, Duration};
 
 use crate::http::Http;
-use serenity::Result;
+use crate::Result;
 
 /// A struct to start typing in a [`Channel`] for an indefinite period of time.
 ///
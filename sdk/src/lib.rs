@@ -2,17 +2,38 @@
 
 use std::future::Future;
 
+pub mod builder;
+pub mod commands;
+pub mod defer;
+pub mod error;
+pub mod filter;
+mod host;
 pub mod http;
 
 pub mod model;
+pub mod router;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod testing;
+
+pub use commands::ApplicationCommands;
+pub use defer::AutoDefer;
+pub use error::{Error, Result};
+pub use filter::EventFilter;
+pub use router::CommandRouter;
 
 use async_trait::async_trait;
-use flowsnet_platform_sdk::write_error_log;
-use http::{Http, HttpBuilder};
-use http_req::request;
+use http::{routing::Route, Http, HttpBuilder, HttpError, LightMethod, TransportRequest};
+use http_req::response::Headers;
 use model::{
-    application::interaction::application_command::ApplicationCommandInteraction, Message,
+    application::interaction::{
+        application_command::ApplicationCommandInteraction, autocomplete::AutocompleteInteraction,
+        message_component::MessageComponentInteraction, modal::ModalSubmitInteraction,
+    },
+    GuildChannel, GuildMemberRemoveEvent, Member, Message, MessageDeleteEvent, MessageUpdateEvent,
+    Reaction, ScheduledEvent, VoiceState,
 };
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
 
 const API_PREFIX: &str = match std::option_env!("DISCORD_API_PREFIX") {
     Some(v) => v,
@@ -21,44 +42,44 @@ const API_PREFIX: &str = match std::option_env!("DISCORD_API_PREFIX") {
 
 const DEFAULT_BOT_PLACEHOLDER: &str = "DEFAULT_BOT";
 
-pub enum EventModel {
-    Message(Message),
-    ApplicationCommand(ApplicationCommandInteraction),
-}
-
-extern "C" {
-    // Flag if current running is for listening(1) or message receving(0)
-    fn is_listening() -> i32;
+/// The header carrying the JWT the flows platform signs for current flow.
+pub(crate) const FLOWS_JWT_HEADER: &str = "X-Flows-Jwt";
 
-    // Return the user id of the flows platform
-    fn get_flows_user(p: *mut u8) -> i32;
-
-    // Return the flow id
-    fn get_flow_id(p: *mut u8) -> i32;
-
-    fn get_event_body_length() -> i32;
-    fn get_event_body(p: *mut u8) -> i32;
-    fn get_event_headers_length() -> i32;
-    fn get_event_headers(p: *mut u8) -> i32;
-    fn set_output(p: *const u8, len: i32);
-    fn set_error_code(code: i16);
-}
-
-pub(crate) unsafe fn _get_flows_user() -> String {
-    let mut flows_user = Vec::<u8>::with_capacity(100);
-    let c = get_flows_user(flows_user.as_mut_ptr());
-    flows_user.set_len(c as usize);
-    String::from_utf8(flows_user).unwrap()
+/// Add the JWT of current flow to the headers of a call to the integration.
+///
+/// The runtime sets it in the `FLOWS_JWT` variable of the wasi environment it
+/// instantiates the flow with. When it is not set, as with the runtimes
+/// predating it or in native tests, the header is left out rather than sent
+/// empty: the integration then refuses the calls, but those of its legacy
+/// routes during their deprecation window.
+pub(crate) fn insert_flows_jwt(headers: &mut Headers) {
+    if let Ok(jwt) = std::env::var("FLOWS_JWT") {
+        headers.insert(FLOWS_JWT_HEADER, &jwt);
+    }
 }
 
-pub(crate) unsafe fn _get_flow_id() -> String {
-    let mut flow_id = Vec::<u8>::with_capacity(100);
-    let c = get_flow_id(flow_id.as_mut_ptr());
-    if c == 0 {
-        panic!("Failed to get flow id");
-    }
-    flow_id.set_len(c as usize);
-    String::from_utf8(flow_id).unwrap()
+/// An event received by the flow.
+///
+/// New kinds of events are added as Discord and the integration support
+/// them, so a `match` on it needs a wildcard arm.
+#[non_exhaustive]
+pub enum EventModel {
+    Message(Message),
+    ApplicationCommand(ApplicationCommandInteraction),
+    MessageComponent(MessageComponentInteraction),
+    ModalSubmit(ModalSubmitInteraction),
+    Autocomplete(AutocompleteInteraction),
+    MessageUpdate(MessageUpdateEvent),
+    MessageDelete(MessageDeleteEvent),
+    ReactionAdd(Reaction),
+    ReactionRemove(Reaction),
+    GuildMemberAddition(Member),
+    GuildMemberRemoval(GuildMemberRemoveEvent),
+    ThreadCreate(GuildChannel),
+    VoiceStateUpdate(VoiceState),
+    GuildScheduledEventCreate(ScheduledEvent),
+    GuildScheduledEventUpdate(ScheduledEvent),
+    GuildScheduledEventDelete(ScheduledEvent),
 }
 
 #[async_trait]
@@ -70,13 +91,15 @@ pub trait Bot {
     /// Before creating the listener, this function will revoke previous
     /// registered listener of current flow so you don't need to do it manually.
     ///
-    /// `callback` is a callback function which will be called when new `Message` is received.
+    /// `callback` is a callback function which will be called when new `EventModel` is received.
     ///
     /// # Example
     ///
-    /// ```rust
-    /// #[tokio::main]
-    /// pub async run() {
+    /// ```rust,no_run
+    /// use discord_flows::{Bot, DefaultBot};
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// pub async fn run() {
     ///     let bot = DefaultBot;
     ///     bot.listen_to_channel(123456, |msg| async {
     ///         todo!()
@@ -88,7 +111,134 @@ pub trait Bot {
         F: FnOnce(EventModel) -> Fut + Send,
         Fut: Future<Output = ()> + Send,
     {
-        listen_to_event(&self.get_token(), Some(channel_id), callback).await;
+        listen_to_event(&self.get_token(), &[channel_id], &[], None, None, callback).await;
+    }
+
+    /// Same as [`listen_to_channel`] but only the events passing `filter` are
+    /// delivered to the flow.
+    ///
+    /// [`listen_to_channel`]: Bot::listen_to_channel
+    async fn listen_to_channel_with_filter<F, Fut>(
+        &self,
+        channel_id: u64,
+        filter: &EventFilter,
+        callback: F,
+    ) where
+        F: FnOnce(EventModel) -> Fut + Send,
+        Fut: Future<Output = ()> + Send,
+    {
+        listen_to_event(
+            &self.get_token(),
+            &[channel_id],
+            &[],
+            Some(filter),
+            None,
+            callback,
+        )
+        .await;
+    }
+
+    /// Create a listener for several channels at once
+    ///
+    /// Calling it again replaces the channels the flow listens to.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use discord_flows::{Bot, DefaultBot};
+    ///
+    /// # async fn run() {
+    /// let bot = DefaultBot;
+    /// bot.listen_to_channels(&[123456, 654321], |msg| async {
+    ///     todo!()
+    /// }).await;
+    /// # }
+    /// ```
+    async fn listen_to_channels<F, Fut>(&self, channel_ids: &[u64], callback: F)
+    where
+        F: FnOnce(EventModel) -> Fut + Send,
+        Fut: Future<Output = ()> + Send,
+    {
+        listen_to_event(&self.get_token(), channel_ids, &[], None, None, callback).await;
+    }
+
+    /// Create a listener for all the channels of the guilds
+    ///
+    /// With [`DefaultBot`], the guilds must have been authorized on
+    /// flows.network.
+    async fn listen_to_guilds<F, Fut>(&self, guild_ids: &[u64], callback: F)
+    where
+        F: FnOnce(EventModel) -> Fut + Send,
+        Fut: Future<Output = ()> + Send,
+    {
+        listen_to_event(&self.get_token(), &[], guild_ids, None, None, callback).await;
+    }
+
+    /// Create a listener for the channels and guilds, and register the
+    /// application `commands` of the bot
+    ///
+    /// The commands are only overwritten when they differ from the ones
+    /// already registered on Discord.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use discord_flows::{builder::CreateCommand, ApplicationCommands, Bot, ProvidedBot};
+    ///
+    /// # async fn run() {
+    /// let bot = ProvidedBot::new("YOUR BOT TOKEN");
+    /// let commands = ApplicationCommands::new().global([
+    ///     CreateCommand::new("ping").description("Check the bot is alive"),
+    /// ]);
+    /// bot.listen_with_commands(&[], &[], &commands, |msg| async {
+    ///     todo!()
+    /// }).await;
+    /// # }
+    /// ```
+    async fn listen_with_commands<F, Fut>(
+        &self,
+        channel_ids: &[u64],
+        guild_ids: &[u64],
+        commands: &ApplicationCommands,
+        callback: F,
+    ) where
+        F: FnOnce(EventModel) -> Fut + Send,
+        Fut: Future<Output = ()> + Send,
+    {
+        listen_to_event(
+            &self.get_token(),
+            channel_ids,
+            guild_ids,
+            None,
+            Some(commands),
+            callback,
+        )
+        .await;
+    }
+
+    /// Remove all the listeners of current flow
+    ///
+    /// The flow stops receiving events and a bot provided by you is
+    /// disconnected once no flow listens to it anymore.
+    fn unlisten(&self) -> Result<()> {
+        let (flows_user, flow_id) = (host::flows_user(), host::flow_id());
+
+        let url = format!("{}/{}/{}/listen", API_PREFIX, flows_user, flow_id);
+        let mut headers = Headers::new();
+        insert_flows_jwt(&mut headers);
+
+        let (res, text) = host::platform_request(TransportRequest {
+            method: LightMethod::Delete,
+            route: Route::None,
+            url: &url,
+            headers: &headers,
+            body: &[],
+        })?;
+
+        match res.status_code().is_success() {
+            true => Ok(()),
+            false => Err(HttpError::from_response(&res, &url, &text).into()),
+        }
     }
 
     /// Get a Discord Client as a bot represented by `bot_token`
@@ -129,13 +279,15 @@ impl ProvidedBot {
 /// Before creating the listener, this function will revoke previous
 /// registered listener of current flow so you don't need to do it manually.
 ///
-/// `callback` is a callback function which will be called when new `Message` is received.
+/// `callback` is a callback function which will be called when new `EventModel` is received.
 ///
 /// # Example
 ///
-/// ```rust
-/// #[tokio::main]
-/// pub async run() {
+/// ```rust,no_run
+/// use discord_flows::ProvidedBot;
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// pub async fn run() {
 ///     let bot = ProvidedBot::new("YOUR BOT TOKEN");
 ///     bot.listen(|msg| async {
 ///         todo!()
@@ -148,108 +300,170 @@ impl ProvidedBot {
         F: FnOnce(EventModel) -> Fut + Send,
         Fut: Future<Output = ()> + Send,
     {
-        listen_to_event(&self.token, None, callback).await;
+        listen_to_event(&self.token, &[], &[], None, None, callback).await;
+    }
+
+    /// Same as [`listen`] but only the events passing `filter` are delivered
+    /// to the flow.
+    ///
+    /// [`listen`]: ProvidedBot::listen
+    pub async fn listen_with_filter<F, Fut>(&self, filter: &EventFilter, callback: F)
+    where
+        F: FnOnce(EventModel) -> Fut + Send,
+        Fut: Future<Output = ()> + Send,
+    {
+        listen_to_event(&self.token, &[], &[], Some(filter), None, callback).await;
     }
 }
 
-async fn listen_to_event<F, Fut>(token: &str, channel_id: Option<u64>, callback: F)
-where
+/// The answer of the integration to a registration.
+#[derive(Default, Deserialize)]
+struct ListenResponse {
+    #[serde(default)]
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// A problem the integration found with the bot of a registration.
+#[derive(Deserialize)]
+struct Diagnostic {
+    kind: String,
+    message: String,
+}
+
+async fn listen_to_event<F, Fut>(
+    token: &str,
+    channel_ids: &[u64],
+    guild_ids: &[u64],
+    filter: Option<&EventFilter>,
+    commands: Option<&ApplicationCommands>,
+    callback: F,
+) where
     F: FnOnce(EventModel) -> Fut + Send,
     Fut: Future<Output = ()> + Send,
 {
-    unsafe {
-        match is_listening() {
-            // Calling register
-            1 => {
-                let flows_user = _get_flows_user();
-                let flow_id = _get_flow_id();
-
-                let mut writer = Vec::new();
-                let res = request::post(
-                    format!(
-                        "{}/{}/{}/{}/listen?bot_token={}",
-                        API_PREFIX,
-                        flows_user,
-                        flow_id,
-                        channel_id.unwrap_or(0),
-                        token,
+    if host::is_listening() {
+        // Calling register
+        let flows_user = host::flows_user();
+        let flow_id = host::flow_id();
+
+        let url = format!("{}/{}/{}/listen", API_PREFIX, flows_user, flow_id);
+        let body = serde_json::to_vec(&json!({
+            "channel_ids": channel_ids,
+            "guild_ids": guild_ids,
+            "filter": filter,
+            "auto_defer": commands.and_then(ApplicationCommands::auto_defer_setting),
+        }))
+        .unwrap();
+
+        let mut headers = Headers::new();
+        headers.insert("X-Discord-Bot-Token", token);
+        insert_flows_jwt(&mut headers);
+        headers.insert("Content-Type", "application/json");
+        headers.insert("Content-Length", &body.len().to_string());
+
+        let (res, text) = host::platform_request(TransportRequest {
+            method: LightMethod::Post,
+            route: Route::None,
+            url: &url,
+            headers: &headers,
+            body: &body,
+        })
+        .unwrap();
+
+        match res.status_code().is_success() {
+            true => {
+                let mut output = match (channel_ids, guild_ids) {
+                    ([], []) => format!(
+                        "[{}] Listening to all channels your bot is on.",
+                        std::env!("CARGO_CRATE_NAME")
                     ),
-                    &[],
-                    &mut writer,
-                )
-                .unwrap();
-
-                match res.status_code().is_success() {
-                    true => {
-                        let output = match channel_id {
-                            Some(c) => format!(
-                                "[{}] Listening to channel `{}`.",
-                                std::env!("CARGO_CRATE_NAME"),
-                                c
-                            ),
-                            None => format!(
-                                "[{}] Listening to all channels your bot is on.",
-                                std::env!("CARGO_CRATE_NAME")
-                            ),
-                        };
-                        set_output(output.as_ptr(), output.len() as i32);
-                    }
-                    false => {
-                        write_error_log!(String::from_utf8_lossy(&writer));
-                        set_error_code(
-                            format!("{}", res.status_code()).parse::<i16>().unwrap_or(0),
-                        );
+                    ([c], []) => format!(
+                        "[{}] Listening to channel `{}`.",
+                        std::env!("CARGO_CRATE_NAME"),
+                        c
+                    ),
+                    _ => format!(
+                        "[{}] Listening to channels {:?} and guilds {:?}.",
+                        std::env!("CARGO_CRATE_NAME"),
+                        channel_ids,
+                        guild_ids
+                    ),
+                };
+                // What may keep the bot from receiving the events, like a
+                // missing intent or permission
+                let response = serde_json::from_str::<ListenResponse>(&text).unwrap_or_default();
+                for Diagnostic { kind, message } in response.diagnostics {
+                    output.push_str(&format!(
+                        "\n[{}] Warning ({}): {}",
+                        std::env!("CARGO_CRATE_NAME"),
+                        kind,
+                        message
+                    ));
+                }
+                host::set_output(&output);
+
+                if let Some(commands) = commands {
+                    let http = HttpBuilder::new(token).build();
+                    let synced = match token == DEFAULT_BOT_PLACEHOLDER {
+                        true => commands.sync_shared(&http).await,
+                        false => commands.sync(&http).await,
+                    };
+                    if let Err(e) = synced {
+                        host::error_log(&format!("Failed to sync the commands: {}", e));
                     }
                 }
             }
-            _ => {
-                if let Some(event) = event_from_subcription() {
-                    callback(event).await;
-                }
+            false => {
+                host::error_log(&text);
+                host::set_error_code(format!("{}", res.status_code()).parse::<i16>().unwrap_or(0));
             }
         }
+    } else if let Some(event) = event_from_subcription() {
+        callback(event).await;
     }
 }
 
 fn event_from_subcription() -> Option<EventModel> {
-    unsafe {
-        let headers = headers_from_subcription().unwrap_or_default();
-        let event_model = headers
-            .into_iter()
-            .find(|(k, _)| k.to_lowercase() == "x-discord-event-model")
-            .unwrap_or((String::new(), String::new()))
-            .1;
-
-        let l = get_event_body_length();
-        let mut event_body = Vec::<u8>::with_capacity(l as usize);
-        let c = get_event_body(event_body.as_mut_ptr());
-        assert!(c == l);
-        event_body.set_len(c as usize);
-
-        match event_model.as_str() {
-            "ApplicationCommand" => {
-                match serde_json::from_slice::<ApplicationCommandInteraction>(&event_body) {
-                    Ok(e) => Some(EventModel::ApplicationCommand(e)),
-                    Err(_) => None,
-                }
-            }
-            "Message" => match serde_json::from_slice::<Message>(&event_body) {
-                Ok(e) => Some(EventModel::Message(e)),
-                Err(_) => None,
-            },
-            _ => None,
+    let headers = headers_from_subcription().unwrap_or_default();
+    let event_model = headers
+        .into_iter()
+        .find(|(k, _)| k.to_lowercase() == "x-discord-event-model")
+        .unwrap_or((String::new(), String::new()))
+        .1;
+
+    let event_body = host::event_body();
+
+    match event_model.as_str() {
+        "ApplicationCommand" => parse_event(&event_body, EventModel::ApplicationCommand),
+        "MessageComponent" => parse_event(&event_body, EventModel::MessageComponent),
+        "ModalSubmit" => parse_event(&event_body, EventModel::ModalSubmit),
+        "Autocomplete" => parse_event(&event_body, EventModel::Autocomplete),
+        "Message" => parse_event(&event_body, EventModel::Message),
+        "MessageUpdate" => parse_event(&event_body, EventModel::MessageUpdate),
+        "MessageDelete" => parse_event(&event_body, EventModel::MessageDelete),
+        "ReactionAdd" => parse_event(&event_body, EventModel::ReactionAdd),
+        "ReactionRemove" => parse_event(&event_body, EventModel::ReactionRemove),
+        "GuildMemberAddition" => parse_event(&event_body, EventModel::GuildMemberAddition),
+        "GuildMemberRemoval" => parse_event(&event_body, EventModel::GuildMemberRemoval),
+        "ThreadCreate" => parse_event(&event_body, EventModel::ThreadCreate),
+        "VoiceStateUpdate" => parse_event(&event_body, EventModel::VoiceStateUpdate),
+        "GuildScheduledEventCreate" => {
+            parse_event(&event_body, EventModel::GuildScheduledEventCreate)
         }
+        "GuildScheduledEventUpdate" => {
+            parse_event(&event_body, EventModel::GuildScheduledEventUpdate)
+        }
+        "GuildScheduledEventDelete" => {
+            parse_event(&event_body, EventModel::GuildScheduledEventDelete)
+        }
+        _ => None,
     }
 }
 
+fn parse_event<T: DeserializeOwned>(body: &[u8], f: fn(T) -> EventModel) -> Option<EventModel> {
+    serde_json::from_slice::<T>(body).ok().map(f)
+}
+
 fn headers_from_subcription() -> Option<Vec<(String, String)>> {
-    unsafe {
-        let l = get_event_headers_length();
-        let mut event_body = Vec::<u8>::with_capacity(l as usize);
-        let c = get_event_headers(event_body.as_mut_ptr());
-        assert!(c == l);
-        event_body.set_len(c as usize);
-
-        serde_json::from_slice(&event_body).ok()
-    }
+    serde_json::from_slice(&host::event_headers()).ok()
 }
@@ -0,0 +1,258 @@
+//! Dispatch of the [`ApplicationCommandInteraction`]s to the handler of their
+//! command.
+//!
+//! [`ApplicationCommandInteraction`]: crate::model::application::interaction::application_command::ApplicationCommandInteraction
+
+use std::{collections::HashMap, error::Error as StdError, fmt, future::Future, pin::Pin};
+
+use crate::model::application::command::CommandOptionType;
+use crate::model::application::interaction::application_command::{
+    ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue,
+};
+use crate::model::{Attachment, PartialChannel, PartialMember, Role, User};
+use crate::{Error, Result};
+
+type BoxedHandler = Box<
+    dyn Fn(
+            ApplicationCommandInteraction,
+            CommandOptions,
+        ) -> Pin<Box<dyn Future<Output = Result<()>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// A router calling the handler registered for the command of an
+/// [`ApplicationCommandInteraction`].
+///
+/// The handlers are registered by the path of their command, the name of
+/// the command followed by the names of its sub command group and sub
+/// command, if any, separated by spaces.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use discord_flows::{router::CommandRouter, Bot, DefaultBot, EventModel};
+///
+/// # async fn run() {
+/// let bot = DefaultBot;
+/// let router = CommandRouter::new()
+///     .route("ping", |ac, _| async move {
+///         todo!()
+///     })
+///     .route("config set", |ac, options| async move {
+///         let key: String = options.get("key")?;
+///         let value: Option<i64> = options.get_optional("value")?;
+///         todo!()
+///     });
+///
+/// bot.listen_to_channel(123456, |event| async move {
+///     if let EventModel::ApplicationCommand(ac) = event {
+///         if let Err(e) = router.dispatch(ac).await {
+///             todo!()
+///         }
+///     }
+/// })
+/// .await;
+/// # }
+/// ```
+///
+/// [`ApplicationCommandInteraction`]: crate::model::application::interaction::application_command::ApplicationCommandInteraction
+#[derive(Default)]
+pub struct CommandRouter {
+    handlers: HashMap<String, BoxedHandler>,
+}
+
+impl CommandRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the `handler` of the command at `path`, replacing the one
+    /// registered before.
+    #[must_use]
+    pub fn route<F, Fut>(mut self, path: &str, handler: F) -> Self
+    where
+        F: Fn(ApplicationCommandInteraction, CommandOptions) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let path = path.split_whitespace().collect::<Vec<_>>().join(" ");
+        self.handlers.insert(
+            path,
+            Box::new(move |ac, options| Box::pin(handler(ac, options))),
+        );
+
+        self
+    }
+
+    /// Call the handler registered for the command of the interaction.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CommandError::UnknownCommand`] when no handler is registered
+    /// for the command, else the error returned by the handler.
+    pub async fn dispatch(&self, ac: ApplicationCommandInteraction) -> Result<()> {
+        let (path, options) = command_path(&ac);
+
+        match self.handlers.get(&path) {
+            Some(handler) => handler(ac, CommandOptions { options }).await,
+            None => Err(CommandError::UnknownCommand(path).into()),
+        }
+    }
+}
+
+/// The path of the command invoked and the options of the sub command
+/// invoked, if any.
+fn command_path(ac: &ApplicationCommandInteraction) -> (String, Vec<CommandDataOption>) {
+    let mut path = ac.data.name.clone();
+    let mut options = &ac.data.options;
+
+    while let [option] = options.as_slice() {
+        match option.kind {
+            CommandOptionType::SubCommandGroup | CommandOptionType::SubCommand => {
+                path.push(' ');
+                path.push_str(&option.name);
+                options = &option.options;
+            }
+            _ => break,
+        }
+    }
+
+    (path, options.clone())
+}
+
+/// The options given to a command, converted into Rust types with
+/// [`get`] and [`get_optional`].
+///
+/// [`get`]: Self::get
+/// [`get_optional`]: Self::get_optional
+#[derive(Clone, Debug)]
+pub struct CommandOptions {
+    options: Vec<CommandDataOption>,
+}
+
+impl CommandOptions {
+    /// Get the value of the required option `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CommandError::MissingOption`] when the option is not given,
+    /// or [`CommandError::MistypedOption`] when it is not a `T`.
+    pub fn get<T: FromCommandOption>(&self, name: &str) -> Result<T> {
+        self.get_optional(name)?
+            .ok_or_else(|| CommandError::MissingOption(name.to_string()).into())
+    }
+
+    /// Get the value of the option `name`, `None` when it is not given.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CommandError::MistypedOption`] when the option is not a `T`.
+    pub fn get_optional<T: FromCommandOption>(&self, name: &str) -> Result<Option<T>> {
+        let resolved = match self.options.iter().find(|o| o.name == name) {
+            Some(option) => option.resolved.as_ref(),
+            None => return Ok(None),
+        };
+
+        match resolved {
+            Some(value) => T::from_command_option(value).map(Some).ok_or_else(|| {
+                CommandError::MistypedOption {
+                    name: name.to_string(),
+                    expected: T::EXPECTED,
+                }
+                .into()
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// The raw options, to read the ones not covered by
+    /// [`FromCommandOption`].
+    pub fn options(&self) -> &[CommandDataOption] {
+        &self.options
+    }
+}
+
+/// A type an option of a command can be converted into.
+pub trait FromCommandOption: Sized {
+    /// The name of the type, used in [`CommandError::MistypedOption`].
+    const EXPECTED: &'static str;
+
+    fn from_command_option(value: &CommandDataOptionValue) -> Option<Self>;
+}
+
+macro_rules! from_command_option {
+    ($ty:ty, $expected:literal, $($pattern:pat => $value:expr),+) => {
+        impl FromCommandOption for $ty {
+            const EXPECTED: &'static str = $expected;
+
+            fn from_command_option(value: &CommandDataOptionValue) -> Option<Self> {
+                match value {
+                    $($pattern => Some($value),)+
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+from_command_option!(String, "string", CommandDataOptionValue::String(s) => s.clone());
+from_command_option!(i64, "integer", CommandDataOptionValue::Integer(i) => *i);
+from_command_option!(
+    f64,
+    "number",
+    CommandDataOptionValue::Number(n) => *n,
+    CommandDataOptionValue::Integer(i) => *i as f64
+);
+from_command_option!(bool, "boolean", CommandDataOptionValue::Boolean(b) => *b);
+from_command_option!(User, "user", CommandDataOptionValue::User(u, _) => u.clone());
+from_command_option!(
+    PartialMember,
+    "member",
+    CommandDataOptionValue::User(_, Some(m)) => m.clone()
+);
+from_command_option!(
+    PartialChannel,
+    "channel",
+    CommandDataOptionValue::Channel(c) => c.clone()
+);
+from_command_option!(Role, "role", CommandDataOptionValue::Role(r) => r.clone());
+from_command_option!(
+    Attachment,
+    "attachment",
+    CommandDataOptionValue::Attachment(a) => a.clone()
+);
+
+/// An error while routing a command or reading its options.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum CommandError {
+    /// No handler is registered for the command at this path.
+    UnknownCommand(String),
+    /// The required option is not given.
+    MissingOption(String),
+    /// The option is not of the expected type.
+    MistypedOption {
+        name: String,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCommand(path) => write!(f, "No handler for the command `{}`", path),
+            Self::MissingOption(name) => write!(f, "Missing the option `{}`", name),
+            Self::MistypedOption { name, expected } => {
+                write!(f, "The option `{}` is not a {}", name, expected)
+            }
+        }
+    }
+}
+
+impl StdError for CommandError {}
+
+impl From<CommandError> for Error {
+    fn from(e: CommandError) -> Error {
+        Error::Command(e)
+    }
+}
@@ -0,0 +1,361 @@
+//! A native stand-in for the flows.network runtime, to drive a flow with
+//! plain `cargo test`.
+//!
+//! A [`TestHost`] plays one run of the flow: either the registration, in
+//! which the `listen_*` functions register the listeners, or the delivery of
+//! an event to the callback. Once installed on the test thread, the flow
+//! reads its inputs from it and writes its output and error code to it, and
+//! every request it sends, to flows.network or to Discord, is recorded by
+//! the [`MockTransport`] of the host.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use discord_flows::{http::MockResponse, testing::TestHost, Bot, DefaultBot, EventModel};
+//! use serde_json::json;
+//!
+//! async fn flow() {
+//!     DefaultBot
+//!         .listen_to_channel(123456, |event| async move {
+//!             if let EventModel::Message(msg) = event {
+//!                 let client = DefaultBot.get_client();
+//!                 let _ = client.send_message(msg.channel_id.0, &json!({ "content": "pong" })).await;
+//!             }
+//!         })
+//!         .await;
+//! }
+//!
+//! #[tokio::test]
+//! async fn registers_the_channel() {
+//!     let host = TestHost::registration();
+//!     host.install();
+//!     host.transport().push(MockResponse::new(200));
+//!
+//!     flow().await;
+//!
+//!     assert_eq!(host.error_code(), None);
+//!     assert!(host.output().unwrap().contains("123456"));
+//! }
+//!
+//! #[tokio::test]
+//! async fn answers_the_messages() {
+//!     let host = TestHost::event("Message", &json!({ /* a Discord message */ }));
+//!     host.install();
+//!
+//!     flow().await;
+//!
+//!     let requests = host.transport().requests();
+//!     assert_eq!(requests[0].json().unwrap()["content"], "pong");
+//! }
+//! ```
+//!
+//! As the crate builds for `wasm32-wasi` by default, the tests run with the
+//! target of the host given explicitly, e.g.
+//! `cargo test --target x86_64-unknown-linux-gnu`.
+
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+
+use crate::http::MockTransport;
+
+thread_local! {
+    static HOST: RefCell<Option<TestHost>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Default)]
+struct HostState {
+    output: Option<String>,
+    error_code: Option<i16>,
+    error_logs: Vec<String>,
+}
+
+/// One run of a flow, see the [module documentation](self).
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct TestHost {
+    listening: bool,
+    flows_user: String,
+    flow_id: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    transport: MockTransport,
+    state: Arc<Mutex<HostState>>,
+}
+
+impl TestHost {
+    fn new(listening: bool) -> Self {
+        Self {
+            listening,
+            flows_user: String::from("test-user"),
+            flow_id: String::from("test-flow"),
+            headers: vec![],
+            body: vec![],
+            transport: MockTransport::new(),
+            state: Arc::default(),
+        }
+    }
+
+    /// A run registering the listeners of the flow.
+    ///
+    /// Script the response of flows.network to the registration on
+    /// [`transport`], else it fails with a 404.
+    ///
+    /// [`transport`]: Self::transport
+    pub fn registration() -> Self {
+        Self::new(true)
+    }
+
+    /// A run delivering the event `body` of the kind `event_model`, the
+    /// name of a variant of [`EventModel`] such as `"Message"`.
+    ///
+    /// [`EventModel`]: crate::EventModel
+    pub fn event(event_model: &str, body: &Value) -> Self {
+        Self::new(false)
+            .header("X-Discord-event-model", event_model)
+            .body(body.to_string().into_bytes())
+    }
+
+    /// Set the flows.network user and the id of the flow, `test-user` and
+    /// `test-flow` by default.
+    pub fn flow(mut self, flows_user: impl Into<String>, flow_id: impl Into<String>) -> Self {
+        self.flows_user = flows_user.into();
+        self.flow_id = flow_id.into();
+
+        self
+    }
+
+    /// Add a header to the event, like `X-Discord-deferred`.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+
+        self
+    }
+
+    /// Set the raw body of the event.
+    pub fn body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+
+        self
+    }
+
+    /// Make this host the runtime of the flows run on the current thread,
+    /// replacing the one installed before.
+    ///
+    /// [`Http`] clients built from then on without a transport send their
+    /// requests to [`transport`].
+    ///
+    /// [`Http`]: crate::http::Http
+    /// [`transport`]: Self::transport
+    pub fn install(&self) {
+        HOST.with(|h| *h.borrow_mut() = Some(self.clone()));
+    }
+
+    /// The transport recording the requests of the flow, to flows.network
+    /// and to Discord, and answering them with the scripted responses.
+    pub fn transport(&self) -> &MockTransport {
+        &self.transport
+    }
+
+    /// The output the flow set, like the listened channels once registered.
+    pub fn output(&self) -> Option<String> {
+        self.state.lock().unwrap().output.clone()
+    }
+
+    /// The error code the flow set, the status of a failed registration.
+    pub fn error_code(&self) -> Option<i16> {
+        self.state.lock().unwrap().error_code
+    }
+
+    /// The errors the flow logged, like the response of a failed
+    /// registration.
+    pub fn error_logs(&self) -> Vec<String> {
+        self.state.lock().unwrap().error_logs.clone()
+    }
+
+    pub(crate) fn is_listening(&self) -> bool {
+        self.listening
+    }
+
+    pub(crate) fn flows_user(&self) -> String {
+        self.flows_user.clone()
+    }
+
+    pub(crate) fn flow_id(&self) -> String {
+        self.flow_id.clone()
+    }
+
+    pub(crate) fn event_body(&self) -> Vec<u8> {
+        self.body.clone()
+    }
+
+    pub(crate) fn event_headers(&self) -> Vec<u8> {
+        serde_json::to_vec(&self.headers).unwrap()
+    }
+
+    pub(crate) fn set_output(&self, output: &str) {
+        self.state.lock().unwrap().output = Some(output.to_string());
+    }
+
+    pub(crate) fn set_error_code(&self, code: i16) {
+        self.state.lock().unwrap().error_code = Some(code);
+    }
+
+    pub(crate) fn error_log(&self, message: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .error_logs
+            .push(message.to_string());
+    }
+}
+
+/// Call `f` with the host installed on the current thread.
+///
+/// # Panics
+///
+/// Panics when no host is installed, the flow is not run by a test.
+pub(crate) fn with_host<T>(f: impl FnOnce(&TestHost) -> T) -> T {
+    HOST.with(|h| match h.borrow().as_ref() {
+        Some(host) => f(host),
+        None => panic!("No TestHost is installed, the flow can only run on flows.network"),
+    })
+}
+
+pub(crate) fn installed_transport() -> Option<MockTransport> {
+    HOST.with(|h| h.borrow().as_ref().map(|host| host.transport.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::http::{LightMethod, MockResponse};
+    use crate::{Bot, DefaultBot, EventModel, ProvidedBot};
+
+    fn message() -> Value {
+        json!({
+            "id": "2",
+            "channel_id": "1",
+            "author": {
+                "id": "3",
+                "username": "user",
+                "discriminator": "0001",
+                "avatar": null,
+            },
+            "content": "ping",
+            "timestamp": "2023-01-01T00:00:00.000Z",
+            "edited_timestamp": null,
+            "tts": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": [],
+            "pinned": false,
+            "type": 0,
+        })
+    }
+
+    #[tokio::test]
+    async fn registers_the_listener() {
+        let host = TestHost::registration().flow("user", "flow");
+        host.install();
+        host.transport().push(MockResponse::new(200));
+
+        DefaultBot
+            .listen_to_channel(1, |_| async { panic!("no event is delivered") })
+            .await;
+
+        assert_eq!(host.error_code(), None);
+        assert!(host.output().unwrap().contains("Listening to channel `1`"));
+
+        let requests = host.transport().requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, LightMethod::Post);
+        assert!(requests[0].url.ends_with("/user/flow/listen"));
+        assert_eq!(
+            requests[0].header("X-Discord-Bot-Token"),
+            Some("DEFAULT_BOT")
+        );
+        assert_eq!(requests[0].json().unwrap()["channel_ids"], json!([1]));
+    }
+
+    #[tokio::test]
+    async fn writes_the_diagnostics_of_the_registration() {
+        let host = TestHost::registration();
+        host.install();
+        host.transport().push(MockResponse::new(200).json(&json!({
+            "diagnostics": [{
+                "kind": "missing_intent",
+                "intent": "Message Content",
+                "message": "The privileged Message Content intent is not enabled",
+            }],
+        })));
+
+        ProvidedBot::new("token")
+            .listen_to_channel(1, |_| async {})
+            .await;
+
+        let output = host.output().unwrap();
+        assert!(output.contains("Listening to channel `1`"));
+        assert!(output.contains(
+            "Warning (missing_intent): The privileged Message Content intent is not enabled"
+        ));
+    }
+
+    #[tokio::test]
+    async fn sets_the_status_of_a_failed_registration() {
+        let host = TestHost::registration();
+        host.install();
+        host.transport()
+            .push(MockResponse::new(401).body("Unauthorized"));
+
+        ProvidedBot::new("token").listen(|_| async {}).await;
+
+        assert_eq!(host.error_code(), Some(401));
+        assert_eq!(host.error_logs(), ["Unauthorized"]);
+        assert_eq!(host.output(), None);
+    }
+
+    #[tokio::test]
+    async fn delivers_the_event() {
+        let host = TestHost::event("Message", &message());
+        host.install();
+        host.transport()
+            .push(MockResponse::new(200).json(&json!({ "id": "4" })));
+
+        DefaultBot
+            .listen_to_channel(1, |event| async move {
+                let msg = match event {
+                    EventModel::Message(msg) => msg,
+                    _ => panic!("not a message"),
+                };
+                assert_eq!(msg.content, "ping");
+
+                let reply = json!({ "content": "pong" });
+                let client = DefaultBot.get_client();
+                let _ = client.send_message(msg.channel_id.0, &reply).await;
+            })
+            .await;
+
+        let requests = host.transport().requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].url.ends_with("/channels/1/messages"));
+        assert_eq!(requests[0].json().unwrap()["content"], "pong");
+    }
+
+    #[tokio::test]
+    async fn ignores_unknown_events() {
+        let host = TestHost::event("Unknown", &json!({}));
+        host.install();
+
+        DefaultBot
+            .listen_to_channel(1, |_| async { panic!("no event is delivered") })
+            .await;
+
+        assert!(host.transport().requests().is_empty());
+    }
+}